@@ -0,0 +1,171 @@
+//! Benchmarks of segmentation, comparing the Viterbi pass of segment_with_model with the
+//! memoized split search that it replaced (ported below as legacy_segment). The port is not a
+//! verbatim copy: it takes its costs from a SegmentationModel instead of the fixed 1/(b-a) and 10,
+//! and it also tries splitting words and odd characters, so that both passes do the same work for
+//! the same model.
+//!
+//! Run with `cargo bench --bench segmentation`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::collections::{HashMap, HashSet};
+use zilib::data;
+use zilib::segmentation::{self, DictionaryModel, SegmentationModel};
+
+const BT_MATCH: i32 = 0;
+const BT_ODD: i32 = -1;
+const BIG_VALUE : f32 = f32::INFINITY;
+
+const TEXT : &str = "我哋今日去咗銅鑼灣行街，順便食咗個下午茶。個天氣好好，唔使帶遮，不過啲人真係好多，\
+    行到腳都軟埋。之後我哋搭巴士返屋企，喺車上面傾咗好耐計，講吓最近返工嘅嘢同埋放假想去邊度玩。\
+    佢話想去日本睇紅葉，我就話不如去台灣食嘢，最後都係未傾得掂，等下個禮拜再講。";
+
+/// Memoized recursive function (i.e. "DP" as we call it) of the legacy implementation, ported to
+/// take its costs from the model.
+fn _dp(
+    a: usize,
+    b: usize,
+    s: &[char],
+    model: &dyn SegmentationModel,
+    dp: &mut HashMap<(usize, usize), f32>,
+    bt: &mut HashMap<(usize, usize), i32>,
+) -> f32 {
+    /*" Recurrence function:
+    f(a,b) = min(
+        word_cost(s[a:b]) if s[a:b] is a word,
+
+        odd_cost(s[a]) if b - a == 1,
+
+        min( f(a, i) + f(i, b) for i in [a..b] )
+    )
+
+    The costs come from the model (see SegmentationModel). A word or an odd character is kept
+    unless splitting it is strictly cheaper.
+    "*/
+
+    assert!(a < b);
+
+    if let Some(&res) = dp.get(&(a, b)) {
+        return res;
+    }
+
+    let mut bt_res = 0;
+    let mut res = BIG_VALUE;
+
+    // if s[a:b] is in the dictionary
+    if let Some(cost) = model.word_cost(&s[a..b].iter().collect::<String>()) {
+        res = cost;
+        bt_res = BT_MATCH;
+    } else if b - a == 1 {
+        res = model.odd_cost(s[a]);
+        bt_res = BT_ODD;
+    }
+
+    let mut split_res = BIG_VALUE;
+    let mut split_bt = 0;
+    for i in a + 1..b {
+        let v = _dp(a, i, s, model, dp, bt) + _dp(i, b, s, model, dp, bt);
+        if v <= split_res { // <= is used here to encourage partitioning at later rather than earlier, to encourage "sticking" of words earlier
+            split_res = v;
+            split_bt = i.try_into().expect("a, b should be small enough to fit into i32.");
+        }
+    }
+    if split_res < res {
+        res = split_res;
+        bt_res = split_bt;
+    }
+
+    dp.insert((a, b), res);
+    bt.insert((a, b), bt_res);
+    res
+}
+
+/// Backtracking function.
+/// Returns a tuple containing: tuples of odd character indices that are not
+/// matched, and indices of the segments (segment's start index).
+fn _bt(
+    a: usize,
+    b: usize,
+    bt: &HashMap<(usize, usize), i32>,
+) -> (Vec<usize>, Vec<usize>) {
+    if bt[&(a, b)] == BT_MATCH {
+        return (vec![], vec![a]);
+    }
+
+    if bt[&(a, b)] == BT_ODD {
+        return (vec![a], vec![a]);
+    }
+
+    let mid = bt[&(a, b)] as usize;
+    let (left_odd, left_seg) = _bt(a, mid, bt);
+    let (right_odd, right_seg) = _bt(mid, b, bt);
+
+    let mut odd = left_odd;
+    odd.extend(right_odd);
+
+    let mut seg = left_seg;
+    seg.extend(right_seg);
+
+    (odd, seg)
+}
+
+/// The split search of segment_with_dictionary before the Viterbi pass, ported to take its costs
+/// from the model.
+fn legacy_segment(phrase: &str, model: &dyn SegmentationModel) -> (Vec<usize>, Vec<usize>) {
+    let chars : Vec<char> = phrase.chars().collect();
+    if chars.is_empty() {
+        return (vec![], vec![]);
+    }
+    let mut dp = HashMap::new();
+    let mut bt = HashMap::new();
+    _dp(0, chars.len(), &chars, model, &mut dp, &mut bt);
+    _bt(0, chars.len(), &bt)
+}
+
+fn initialize() {
+    data::initialize_data(data::DataKind::CantoneseCharListWithJyutping, concat!(env!("CARGO_MANIFEST_DIR"), "/lists/charlist.json"));
+    data::initialize_data(data::DataKind::CantoneseWordListWithJyutping, concat!(env!("CARGO_MANIFEST_DIR"), "/lists/wordslist.csv"));
+}
+
+fn bench_segmentation(c: &mut Criterion) {
+    initialize();
+    let dictionary : HashSet<String> = data::cantonese_wordlist_with_jyutping().keys().cloned().collect();
+    let dictionary_model = DictionaryModel::new(&dictionary);
+    let unigram_model = segmentation::default_unigram_model();
+    let chars : Vec<char> = TEXT.chars().collect();
+
+    let mut group = c.benchmark_group("segmentation");
+    for len in [25, 50, 100, 200] {
+        let text : String = chars.iter().cycle().take(len).collect();
+        group.bench_with_input(BenchmarkId::new("viterbi/dictionary", len), &text, |b, text| {
+            b.iter(|| segmentation::segment_with_model(black_box(text), &dictionary_model))
+        });
+        group.bench_with_input(BenchmarkId::new("legacy/dictionary", len), &text, |b, text| {
+            b.iter(|| legacy_segment(black_box(text), &dictionary_model))
+        });
+        group.bench_with_input(BenchmarkId::new("viterbi/unigram", len), &text, |b, text| {
+            b.iter(|| segmentation::segment_with_model(black_box(text), unigram_model))
+        });
+        group.bench_with_input(BenchmarkId::new("legacy/unigram", len), &text, |b, text| {
+            b.iter(|| legacy_segment(black_box(text), unigram_model))
+        });
+    }
+    group.finish();
+
+    let text : String = chars.iter().cycle().take(200).collect();
+    c.bench_function("segmentation_n_best/viterbi/unigram/200/10", |b| {
+        b.iter(|| segmentation::segment_n_best_with_model(black_box(&text), 10, unigram_model))
+    });
+
+    // The Viterbi pass is linear, so it can also segment long texts
+    let mut group = c.benchmark_group("segmentation_long");
+    for len in [1000, 10000] {
+        let text : String = chars.iter().cycle().take(len).collect();
+        group.bench_with_input(BenchmarkId::new("viterbi/unigram", len), &text, |b, text| {
+            b.iter(|| segmentation::segment_with_model(black_box(text), unigram_model))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_segmentation);
+criterion_main!(benches);
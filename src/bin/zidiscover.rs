@@ -0,0 +1,78 @@
+// Read a corpus from files (or stdin) and list candidate new words that are not in the word list,
+// from the best to the worst.
+
+use std::io::BufRead;
+use zilib::{data, discovery};
+
+fn usage() {
+    eprintln!("Usage: zidiscover [--wordlist PATH] [--min-len N] [--max-len N] [--min-count N] [--min-cohesion X] [--min-entropy X] [--top N] [FILE...]");
+    eprintln!("Find candidate new words in the input (the files, or stdin if there are none). Prints the");
+    eprintln!("candidates as tab-separated word, score, count, cohesion, left entropy and right entropy.");
+    eprintln!("--wordlist is required unless zilib is built with the downloaded_data feature.");
+    std::process::exit(1);
+}
+
+fn parse<T: std::str::FromStr>(value: Option<String>) -> T {
+    value.and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+        usage();
+        unreachable!()
+    })
+}
+
+fn add_lines<R: BufRead>(discoverer: &mut discovery::WordDiscoverer, reader: R) {
+    for line in reader.lines() {
+        match line {
+            Ok(line) => discoverer.add_line(&line),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn main() {
+    let mut options = discovery::DiscoveryOptions::default();
+    let mut top = None;
+    let mut has_wordlist = false;
+    let mut files = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--wordlist" => {
+                data::initialize_data(data::DataKind::CantoneseWordListWithJyutping, &parse::<String>(args.next()));
+                has_wordlist = true;
+            },
+            "--min-len" => options.min_len = parse(args.next()),
+            "--max-len" => options.max_len = parse(args.next()),
+            "--min-count" => options.min_count = parse(args.next()),
+            "--min-cohesion" => options.min_cohesion = parse(args.next()),
+            "--min-entropy" => options.min_entropy = parse(args.next()),
+            "--top" => top = Some(parse::<usize>(args.next())),
+            _ if arg.starts_with('-') => usage(),
+            _ => files.push(arg),
+        }
+    }
+
+    if !has_wordlist && !cfg!(feature = "downloaded_data") {
+        usage();
+    }
+
+    let mut discoverer = discovery::WordDiscoverer::new(options);
+    if files.is_empty() {
+        add_lines(&mut discoverer, std::io::stdin().lock());
+    }
+    for file in files {
+        match std::fs::File::open(&file) {
+            Ok(f) => add_lines(&mut discoverer, std::io::BufReader::new(f)),
+            Err(e) => {
+                eprintln!("error: {}: {}", file, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    for c in discoverer.candidates().into_iter().take(top.unwrap_or(usize::MAX)) {
+        println!("{}\t{:.3}\t{}\t{:.3}\t{:.3}\t{:.3}", c.text, c.score, c.count, c.cohesion, c.left_entropy, c.right_entropy);
+    }
+}
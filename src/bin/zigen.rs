@@ -10,7 +10,7 @@ use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write, Seek};
-use zilib::{cjk, common, data};
+use zilib::{cjk, data};
 
 /*
 # More details about file format of varcon can be found in:
@@ -117,35 +117,7 @@ fn generate_english_variants(out_filename : &str) -> io::Result<()> {
 }
 
 fn wordshk_character_set() -> HashSet<char> {
-    let mut resultset = HashSet::new();
-    let set_files = vec!["./lists/edb_charlist.txt", "./lists/wordshk_charlist.txt"];
-    for f in set_files {
-        let file = File::open(f).expect(format!("File {} not found", f).as_str());
-        for line in BufReader::new(file).lines() {
-            let line = line.expect(format!("Error reading file {}", f).as_str());
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-            if line.starts_with('#') {
-                continue;
-            }
-            if line.starts_with('!') {
-                for c in line.chars().skip(1) {
-                    if common::is_cjk_cp(c as u32) {
-                        resultset.remove(&c);
-                    }
-                }
-            } else {
-                for c in line.chars() {
-                    if common::is_cjk_cp(c as u32) {
-                        resultset.insert(c);
-                    }
-                }
-            }
-        }
-    }
-    resultset
+    data::build_wordshk_charset(&["./lists/edb_charlist.txt", "./lists/wordshk_charlist.txt"])
 }
 
 fn generate_wordshk_charset(out_filename : &str) -> io::Result<()> {
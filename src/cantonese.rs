@@ -1,5 +1,8 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::sync::OnceLock;
-use crate::{data, segmentation};
+use crate::{common, data, normalization, segmentation, user_dictionary};
+use crate::jyutping::{self, Syllable};
 use regex::Regex;
 
 /// Gets the pronunciation of a Cantonese string from charlist.
@@ -8,47 +11,520 @@ pub fn get_ping3jam1_from_charlist(chars:Vec<char>) -> Vec<Vec<String>> {
     chars.into_iter().map(|ch| charlist.get(&ch).map(|ps| ps.keys().map(|p| p.clone()).collect()).unwrap_or(vec![])).collect()
 }
 
-/// Gets the pronunciation of a Cantonese string from charlist, picking the most common pronunciation.
-fn get_ping3jam1_from_charlist_most_common(chars:Vec<char>) -> Vec<String> {
-    let charlist = data::cantonese_charlist_with_jyutping();
-    chars.into_iter().map(|ch| charlist.get(&ch).map(|ps| ps.iter().max_by_key(|(_, &count)| count).map(|(p, _)| p.clone()).unwrap_or("".to_string())).unwrap_or("".to_string())).collect()
+/// Same as get_ping3jam1_from_charlist, but returns the pronunciations as syllables.
+pub fn get_ping3jam1_syllables_from_charlist(chars:Vec<char>) -> Vec<Vec<Syllable>> {
+    get_ping3jam1_from_charlist(chars).into_iter().map(|ps| ps.iter().filter_map(|p| p.parse().ok()).collect()).collect()
 }
 
-/// Gets the pronunciation of a Cantonese string from wordlist by first segmenting the string.
-fn get_ping3jam1_from_wordlist(s: &str) -> Vec<String> {
+/// Where the reading of a span in get_ping3jam1_spans comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingSource {
+    /// A word (or single character entry) of wordlist.
+    WordList,
+    /// The readings of a character in charlist.
+    CharList,
+    /// No reading is known, e.g. for punctuation and spaces.
+    Unknown,
+    /// Text that is read out by normalization (e.g. "$1,500" as 一千五百蚊, see
+    /// normalization::normalize_text), which is then read from wordlist and charlist.
+    Normalized,
+    /// A pronunciation forced by a user dictionary (see user_dictionary::UserEntry).
+    UserDictionary,
+}
+
+impl ReadingSource {
+    /// The name of the source, i.e. "wordlist", "charlist", "unknown", "normalized" or "user".
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReadingSource::WordList => "wordlist",
+            ReadingSource::CharList => "charlist",
+            ReadingSource::Unknown => "unknown",
+            ReadingSource::Normalized => "normalized",
+            ReadingSource::UserDictionary => "user",
+        }
+    }
+}
+
+/// A span of the input of get_ping3jam1_spans with its reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ping3jam1Span {
+    /// The text of the span.
+    pub text: String,
+    /// The index of the first character of the span in the input (in characters, not bytes).
+    pub start: usize,
+    /// The index after the last character of the span in the input (in characters, not bytes).
+    pub end: usize,
+    /// The syllables of the chosen reading, which is empty if the source is Unknown. There is
+    /// usually one syllable per character, but not always for words with exceptions (e.g.
+    /// ["!bas4"] for 巴士).
+    pub readings: Vec<String>,
+    /// Where the reading comes from.
+    pub source: ReadingSource,
+}
+
+/// The reading of a segment in get_ping3jam1_candidates, as (segment, candidates, confidence,
+/// source). The candidates are the readings of each part of the segment that is read as a whole (a
+/// word or a character) with their probabilities, from the most likely to the least likely, so the
+/// first candidates make up the pronunciation of the segment. Segments without a known
+/// pronunciation have no parts.
+type SegmentCandidates = (String, Vec<Vec<(String, f64)>>, f64, ReadingSource);
+
+/// Gets the pronunciation of a Cantonese string from wordlist by first normalizing (see
+/// normalization::normalize_text) and segmenting the string. Returns (segment, pronunciation,
+/// confidence, source) for every segment, so the segments add up to the string. Normalized parts of
+/// the string are returned as one segment with the source Normalized, together with the segments
+/// of the normalized text that overlap them (e.g. "12月" if 十二月 is a word). If dictionary is
+/// given, its words and forced pronunciations are used.
+fn get_ping3jam1_segments(s: &str, dictionary: Option<&user_dictionary::LayeredDictionary>) -> Vec<(String, String, f64, ReadingSource)> {
+    get_ping3jam1_candidates(s, dictionary).into_iter().map(|(segment, candidates, confidence, source)| {
+        let pronunciation = candidates.iter().map(|c| c[0].0.as_str()).collect::<Vec<&str>>().join(" ");
+        (segment, pronunciation, confidence, source)
+    }).collect()
+}
+
+/// Same as get_ping3jam1_segments, but returns the candidate readings of every segment (see
+/// SegmentCandidates).
+fn get_ping3jam1_candidates(s: &str, dictionary: Option<&user_dictionary::LayeredDictionary>) -> Vec<SegmentCandidates> {
+    // The pieces of the string, as (original text, whether it was normalized). Unchanged pieces
+    // are split into characters, so that they line up with the segments of the normalized string.
+    let mut pieces : Vec<(String, bool)> = vec![];
+    // The index of the piece of each character of the normalized string
+    let mut owners : Vec<usize> = vec![];
+    let mut normalized = String::new();
+    let text_pieces = match dictionary {
+        Some(dictionary) => normalization::normalize_text_pieces_with_model(s, dictionary.segmenter()),
+        None => normalization::normalize_text_pieces(s),
+    };
+    for (original, text) in text_pieces {
+        if original == text {
+            for ch in original.chars() {
+                owners.push(pieces.len());
+                pieces.push((ch.to_string(), false));
+            }
+        } else {
+            owners.extend(std::iter::repeat_n(pieces.len(), text.chars().count()));
+            pieces.push((original, true));
+        }
+        normalized.push_str(&text);
+    }
+
+    let mut result = vec![];
+    let mut pending : Vec<SegmentCandidates> = vec![];
+    let (mut start, mut pos) = (0, 0);
+    for segment in get_ping3jam1_segments_normalized(&normalized, dictionary) {
+        if pending.is_empty() {
+            start = pos;
+        }
+        pos += segment.0.chars().count();
+        pending.push(segment);
+        // Wait until the segments end at the end of a piece
+        if pos < owners.len() && owners[pos] == owners[pos - 1] {
+            continue;
+        }
+        let (first, last) = (owners[start], owners[pos - 1]);
+        if !pieces[first..=last].iter().any(|(_, changed)| *changed) {
+            result.append(&mut pending);
+            continue;
+        }
+        let text : String = pieces[first..=last].iter().map(|(text, _)| text.as_str()).collect();
+        let candidates = pending.iter().flat_map(|(_, c, _, _)| c.iter().cloned()).collect();
+        let confidence = pending.iter().map(|(_, _, c, _)| *c).fold(1.0, f64::min);
+        result.push((text, candidates, confidence, ReadingSource::Normalized));
+        pending.clear();
+    }
+    result
+}
+
+/// Same as get_ping3jam1_candidates, but for a string that is already normalized. Multi-character
+/// segments use the pronunciations in wordlist (see wordlist_candidates), and single characters are
+/// disambiguated with context (see contextual_candidates). Words of the dictionary that are not in
+/// wordlist are read character by character. Segments without a known pronunciation (e.g. Latin
+/// words, emoji and punctuation) have no candidates.
+fn get_ping3jam1_segments_normalized(s: &str, dictionary: Option<&user_dictionary::LayeredDictionary>) -> Vec<SegmentCandidates> {
     let wordlist = data::cantonese_wordlist_with_jyutping();
-    let (_, _, segments) = segmentation::end_user_friendly_segment(s, None);
+    let (_, _, segments) = match dictionary {
+        Some(dictionary) => dictionary.segmenter().end_user_friendly_segment(s),
+        None => segmentation::end_user_friendly_segment(s, None),
+    };
+    let chars : Vec<char> = segments.iter().flat_map(|segment| segment.chars()).collect();
 
     let mut result = vec![];
+    let mut pos = 0;
     for segment in segments {
-        // The segmentation result is 1 character, so we should use the most common
-        // character instead.
-        let word_pronunciation = wordlist.get(&segment).map(|ps| ps.iter().map(|p| p.clone()));
-        if segment.chars().count() > 1 || word_pronunciation.is_some() {
-            result.push(word_pronunciation.unwrap().next().unwrap()); // safe because the segmentation ensures that the word exists
+        let len = segment.chars().count();
+        if let Some(pronunciation) = dictionary.and_then(|dictionary| dictionary.pronunciation(&segment)) {
+            result.push((segment, vec![vec![(pronunciation.to_string(), 1.0)]], 1.0, ReadingSource::UserDictionary));
+        } else if let Some(pronunciations) = wordlist.get(&segment).filter(|ps| len > 1 && !ps.is_empty()) {
+            let candidates : Vec<(String, f64)> = wordlist_candidates(pronunciations).into_iter().map(|(p, lp)| (p, lp.exp())).collect();
+            let confidence = candidates[0].1;
+            result.push((segment, vec![candidates], confidence, ReadingSource::WordList));
+        } else if len > 1 {
+            // User words that are not in wordlist are read character by character. Latin words,
+            // numbers, URLs etc. that are not words (see segmentation::TokenKind) have no reading.
+            let candidates : Vec<Vec<(String, f64)>> = (pos..pos + len).map(|i| contextual_candidates(&chars, i)).collect();
+            if candidates.iter().all(|c| !c.is_empty()) {
+                let confidence = candidates.iter().map(|c| c[0].1).fold(1.0, f64::min);
+                result.push((segment, candidates, confidence, ReadingSource::CharList));
+            } else {
+                result.push((segment, vec![], 0.0, ReadingSource::Unknown));
+            }
         } else {
-            for cps in get_ping3jam1_from_charlist_most_common(segment.chars().collect()) {
-                result.push(cps);
+            let source = if wordlist.get(&segment).is_some_and(|ps| !ps.is_empty()) {
+                ReadingSource::WordList
+            } else {
+                ReadingSource::CharList
+            };
+            let candidates = contextual_candidates(&chars, pos);
+            match candidates.first().map(|(_, confidence)| *confidence) {
+                Some(confidence) => result.push((segment, vec![candidates], confidence, source)),
+                None => result.push((segment, vec![], 0.0, ReadingSource::Unknown)),
             }
         }
+        pos += len;
     }
     result
 }
 
-/// Gets the pronunciation of a Cantonese string on a best effort basis. Each returned result
-/// corresponds to a character in the input string. IF there are multiple pronunciations for a
-/// character, multiple results are returned. If a character is not found in the dictionary, an
-/// empty list is returned.
+/// Gets the pronunciation of a Cantonese string from wordlist by first segmenting the string.
+fn get_ping3jam1_from_wordlist(s: &str) -> Vec<String> {
+    get_ping3jam1_segments(s, None).into_iter().map(|(_, p, _, _)| p).collect()
+}
+
+/// Gets the pronunciation of a Cantonese string on a best effort basis, as syllables separated by
+/// spaces. The string is segmented into words of wordlist, and each word gets its most common
+/// pronunciation. Characters without a known pronunciation (e.g. punctuation) give empty strings,
+/// so they only show up as extra spaces. Use get_ping3jam1_spans to align the pronunciation with
+/// the string.
 pub fn get_ping3jam1(s: &str) -> String {
     get_ping3jam1_from_wordlist(s).join(" ")
 }
 
+/// Same as get_ping3jam1, but the string is segmented with the words of the dictionary, and words
+/// with a forced pronunciation in the dictionary are read with it.
+pub fn get_ping3jam1_with_dictionary(s: &str, dictionary: &user_dictionary::LayeredDictionary) -> String {
+    get_ping3jam1_segments(s, Some(dictionary)).into_iter().map(|(_, p, _, _)| p).collect::<Vec<String>>().join(" ")
+}
+
+/// Same as get_ping3jam1, but returns the reading of every span of the string (a word of wordlist
+/// or a single character), so that the syllables can be aligned with the input. The spans cover
+/// the whole string in order, including punctuation, spaces and other characters without a known
+/// reading, which have the source Unknown.
+pub fn get_ping3jam1_spans(s: &str) -> Vec<Ping3jam1Span> {
+    let mut start = 0;
+    get_ping3jam1_segments(s, None).into_iter().map(|(text, pronunciation, _, source)| {
+        let end = start + text.chars().count();
+        let readings = pronunciation.split_whitespace().map(|p| p.to_string()).collect();
+        let span = Ping3jam1Span { text, start, end, readings, source };
+        start = end;
+        span
+    }).collect()
+}
+
+/// Same as get_ping3jam1, but returns the pronunciation of each character with a confidence score
+/// (0-1), as (character, pronunciation, confidence). The confidence of a single character is the
+/// probability of its reading given its neighbours (see contextual_candidates), and the confidence
+/// of the characters of a word is the weight of the word's first pronunciation in wordlist. If the
+/// pronunciation of a word doesn't have one syllable per character (e.g. "!bas4" for 巴士), the
+/// whole pronunciation is given to the first character. Characters without a known pronunciation
+/// have an empty pronunciation and a confidence of 0.
+pub fn get_ping3jam1_with_confidence(s: &str) -> Vec<(char, String, f64)> {
+    let mut result = vec![];
+    for (segment, pronunciation, confidence, _) in get_ping3jam1_segments(s, None) {
+        let syllables : Vec<&str> = pronunciation.split_whitespace().collect();
+        if syllables.len() == segment.chars().count() {
+            result.extend(segment.chars().zip(syllables).map(|(ch, p)| (ch, p.to_string(), confidence)));
+        } else {
+            for (i, ch) in segment.chars().enumerate() {
+                result.push((ch, if i == 0 { pronunciation.clone() } else { "".to_string() }, confidence));
+            }
+        }
+    }
+    result
+}
+
+/// Same as get_ping3jam1, but returns the pronunciation as syllables. Characters without a known
+/// pronunciation and pronunciations that are not valid Jyutping (e.g. exceptions such as "!bas4")
+/// are left out.
+pub fn get_ping3jam1_syllables(s: &str) -> Vec<Syllable> {
+    get_ping3jam1_from_wordlist(s).iter().flat_map(|p| p.split_whitespace()).filter_map(|p| p.parse().ok()).collect()
+}
+
+/// The words of wordlist with their parsed pronunciations. Pronunciations that are not valid
+/// Jyutping (e.g. exceptions such as "!bas4") are left out.
+pub(crate) fn parsed_wordlist() -> &'static Vec<(&'static str, &'static str, Vec<Syllable>)> {
+    static DATA: OnceLock<Vec<(&'static str, &'static str, Vec<Syllable>)>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let mut words = vec![];
+        for (word, pronunciations) in data::cantonese_wordlist_with_jyutping() {
+            for p in pronunciations {
+                if let Some(syllables) = jyutping::parse_jyutping(p) {
+                    words.push((word.as_str(), p.as_str(), syllables));
+                }
+            }
+        }
+        words
+    })
+}
+
+// Smoothing for the context counts in contextual_candidates
+const CONTEXT_SMOOTHING : f64 = 0.5;
+
+type ContextCounts = HashMap<(char, char, i8), HashMap<Syllable, u32>>;
+
+/// Bigram statistics of polyphonic characters in wordlist, as (character, neighbouring character,
+/// offset of the neighbour (-1 or 1)) => (reading of the character => number of words).
+fn context_counts() -> &'static ContextCounts {
+    static DATA: OnceLock<ContextCounts> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let charlist = data::cantonese_charlist_with_jyutping();
+        let mut counts : HashMap<(char, char, i8), HashMap<Syllable, u32>> = HashMap::new();
+        for (word, _, syllables) in parsed_wordlist() {
+            let chars : Vec<char> = word.chars().collect();
+            if chars.len() != syllables.len() {
+                continue;
+            }
+            for (i, syllable) in syllables.iter().enumerate() {
+                if charlist.get(&chars[i]).map_or(0, |ps| ps.len()) < 2 {
+                    continue;
+                }
+                if i > 0 {
+                    *counts.entry((chars[i], chars[i - 1], -1)).or_default().entry(*syllable).or_default() += 1;
+                }
+                if i + 1 < chars.len() {
+                    *counts.entry((chars[i], chars[i + 1], 1)).or_default().entry(*syllable).or_default() += 1;
+                }
+            }
+        }
+        counts
+    })
+}
+
+/// Candidate readings of the character at position i, with their probabilities given the
+/// neighbouring characters, from the most likely to the least likely. The prior comes from the
+/// character's own entry in wordlist if there is one (see wordlist_candidates), or charlist
+/// otherwise, and is combined with how often each reading is used next to the neighbours in the
+/// words of wordlist (see context_counts). Neighbours that are never seen next to the character
+/// don't change the prior.
+fn contextual_candidates(chars: &[char], i: usize) -> Vec<(String, f64)> {
+    let ch = chars[i];
+    let mut candidates = match data::cantonese_wordlist_with_jyutping().get(&ch.to_string()).filter(|ps| !ps.is_empty()) {
+        Some(ps) => wordlist_candidates(ps),
+        None => charlist_candidates(ch),
+    };
+    if candidates.len() < 2 {
+        return candidates.into_iter().map(|(p, _)| (p, 1.0)).collect();
+    }
+
+    let k = candidates.len() as f64;
+    for (offset, neighbour) in [(-1, i.checked_sub(1)), (1, Some(i + 1))] {
+        let Some(counts) = neighbour.and_then(|j| chars.get(j)).and_then(|n| context_counts().get(&(ch, *n, offset))) else {
+            continue;
+        };
+        let total : u32 = counts.values().sum();
+        for (p, score) in candidates.iter_mut() {
+            let count = p.parse::<Syllable>().ok().and_then(|s| counts.get(&s)).copied().unwrap_or(0);
+            *score += ((count as f64 + CONTEXT_SMOOTHING) / (total as f64 + CONTEXT_SMOOTHING * k)).ln();
+        }
+    }
+
+    // Normalize the scores into probabilities. The sort is stable, so ties keep the prior order.
+    let max = candidates.iter().map(|(_, score)| *score).fold(f64::NEG_INFINITY, f64::max);
+    let sum : f64 = candidates.iter().map(|(_, score)| (score - max).exp()).sum();
+    let mut candidates : Vec<(String, f64)> = candidates.into_iter().map(|(p, score)| (p, (score - max).exp() / sum)).collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    candidates
+}
+
+/// Changed tones (變調) attested in wordlist, as (character, syllable with the changed tone) => (base
+/// syllable). A changed tone is attested when a word is listed with two pronunciations that only
+/// differ in changed tones (e.g. 金魚 as both "gam1 jyu2" and "gam1 jyu4"). Since some characters
+/// have both tones as readings of their own (e.g. 好 hou2 and hou3), pairs that are attested in
+/// fewer than 1% of the words where the character has the changed tone are left out.
+fn attested_changed_tones() -> &'static HashMap<(char, Syllable), Syllable> {
+    static DATA: OnceLock<HashMap<(char, Syllable), Syllable>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let mut pairs : HashMap<(char, Syllable, Syllable), usize> = HashMap::new();
+        let mut uses : HashMap<(char, Syllable), usize> = HashMap::new();
+        for (word, pronunciations) in data::cantonese_wordlist_with_jyutping() {
+            let chars : Vec<char> = word.chars().collect();
+            if !chars.iter().all(|c| common::is_cjk_cp(*c as u32)) {
+                continue;
+            }
+            let parsed : Vec<Vec<Syllable>> = pronunciations.iter()
+                .filter_map(|p| jyutping::parse_jyutping(p))
+                .filter(|syllables| syllables.len() == chars.len())
+                .collect();
+            for syllables in &parsed {
+                for (ch, syllable) in chars.iter().zip(syllables) {
+                    *uses.entry((*ch, *syllable)).or_default() += 1;
+                }
+            }
+            for changed in &parsed {
+                for base in &parsed {
+                    let diffs : Vec<usize> = (0..chars.len()).filter(|&i| changed[i] != base[i]).collect();
+                    if !diffs.is_empty() && diffs.iter().all(|&i| changed[i].is_changed_tone_of(&base[i])) {
+                        for i in diffs {
+                            *pairs.entry((chars[i], changed[i], base[i])).or_default() += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Keep the most attested base for each changed tone
+        let mut result : HashMap<(char, Syllable), (Syllable, usize)> = HashMap::new();
+        for ((ch, changed, base), count) in pairs {
+            if count * 100 < uses[&(ch, changed)] {
+                continue;
+            }
+            let best = result.entry((ch, changed)).or_insert((base, count));
+            if count > best.1 || (count == best.1 && base < best.0) {
+                *best = (base, count);
+            }
+        }
+        result.into_iter().map(|(k, (base, _))| (k, base)).collect()
+    })
+}
+
+/// If the pronunciation of the character is a changed tone (變調), returns the base syllable. The
+/// changed tones attested in wordlist are used first (e.g. 魚 "jyu2" gives "jyu4"). Otherwise, if
+/// the pronunciation is not one of the character's readings in charlist, but a changed tone of one
+/// of them (see jyutping::is_changed_tone), the most common such reading is returned.
+pub fn changed_tone_base(ch: char, pronunciation: &str) -> Option<Syllable> {
+    let changed : Syllable = pronunciation.parse().ok()?;
+    if let Some(base) = attested_changed_tones().get(&(ch, changed)) {
+        return Some(*base);
+    }
+    let readings = data::cantonese_charlist_with_jyutping().get(&ch)?;
+    if readings.contains_key(pronunciation) {
+        return None;
+    }
+    readings.iter()
+        .filter_map(|(p, &count)| Some((p.parse::<Syllable>().ok()?, count)))
+        .filter(|(base, _)| changed.is_changed_tone_of(base))
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(base, _)| base)
+}
+
+/// Same as get_ping3jam1, but syllables that are pronounced with a changed tone (變調) are annotated
+/// with the base tone, as "base*changed" (e.g. "mui6*2" for 妹 pronounced as mui2). See
+/// changed_tone_base for how changed tones are detected.
+pub fn get_ping3jam1_with_changed_tones(s: &str) -> String {
+    get_ping3jam1_segments(s, None).iter().map(|(segment, pronunciation, _, source)| {
+        let syllables : Vec<&str> = pronunciation.split_whitespace().collect();
+        // Can't tell which character a syllable belongs to (e.g. for exceptions like "!bas4" and
+        // normalized text like "$1,500")
+        if syllables.len() != segment.chars().count() || *source == ReadingSource::Normalized {
+            return pronunciation.clone();
+        }
+        segment.chars().zip(syllables).map(|(ch, p)| match changed_tone_base(ch, p) {
+            Some(base) => format!("{}*{}", base, &p[p.len() - 1..]),
+            None => p.to_string(),
+        }).collect::<Vec<String>>().join(" ")
+    }).collect::<Vec<String>>().join(" ")
+}
+
+/// Candidate pronunciations of a character from charlist, sorted from the most likely to the least
+/// likely, with their log probabilities.
+fn charlist_candidates(ch: char) -> Vec<(String, f64)> {
+    let charlist = data::cantonese_charlist_with_jyutping();
+    let Some(ps) = charlist.get(&ch) else {
+        return vec![];
+    };
+    let total : u64 = ps.values().sum();
+    let mut candidates : Vec<(String, u64)> = ps.iter().map(|(p, &count)| (p.clone(), count)).collect();
+    // Sort by the pronunciation too so that ties have a stable order
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    candidates.into_iter().map(|(p, count)| (p, (count.max(1) as f64 / total.max(1) as f64).ln())).collect()
+}
+
+/// Candidate pronunciations of a word from wordlist, with their log probabilities. The word list
+/// doesn't have counts, so we assume the pronunciations listed earlier are more common, and give
+/// the i-th pronunciation a weight of 1/(i+1).
+fn wordlist_candidates(pronunciations: &[String]) -> Vec<(String, f64)> {
+    let total : f64 = (1..=pronunciations.len()).map(|i| 1.0 / i as f64).sum();
+    pronunciations.iter().enumerate().map(|(i, p)| (p.clone(), (1.0 / (i + 1) as f64 / total).ln())).collect()
+}
+
+/// A combination of candidates (one per part of the string) in the k-best search of
+/// get_n_ping3jam1, as the index of the chosen candidate of each part
+struct Combination {
+    log_probability: f64,
+    choices: Vec<usize>,
+}
+
+impl PartialEq for Combination {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Combination {}
+
+impl PartialOrd for Combination {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Combination {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher probabilities first. For ties, prefer the combination with the more common
+        // pronunciations in the earlier parts.
+        self.log_probability.total_cmp(&other.log_probability)
+            .then_with(|| other.choices.cmp(&self.choices))
+    }
+}
+
+/// Gets the n most likely pronunciations of a Cantonese string, from the most likely to the least
+/// likely. The string is segmented and each part of it (a word or a character) gets its candidate
+/// readings with their probabilities in the same way as in get_ping3jam1, so the first result is
+/// the same as get_ping3jam1 (without the extra spaces of characters without a pronunciation). The
+/// likelihood of a pronunciation is the product of the probabilities of the readings of its parts.
+/// Fewer than n results are returned if there aren't enough combinations.
+pub fn get_n_ping3jam1(s: &str, n: usize) -> Vec<String> {
+    let candidates : Vec<Vec<(String, f64)>> = get_ping3jam1_candidates(s, None).into_iter().flat_map(|(_, c, _, _)| c).collect();
+    if n == 0 || candidates.is_empty() {
+        return vec![];
+    }
+
+    // Best-first search over the combinations: we start from the most likely combination, and
+    // each popped combination adds its neighbours (one part moved to its next candidate) to
+    // the queue. Since the candidates are sorted, the neighbours can never be more likely.
+    let log_probability = |choices: &Vec<usize>| -> f64 {
+        choices.iter().enumerate().map(|(i, &c)| candidates[i][c].1.ln()).sum()
+    };
+    let mut heap = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    let start = vec![0; candidates.len()];
+    seen.insert(start.clone());
+    heap.push(Combination { log_probability: log_probability(&start), choices: start });
+
+    let mut result = vec![];
+    while let Some(Combination { choices, .. }) = heap.pop() {
+        result.push(choices.iter().enumerate().map(|(i, &c)| candidates[i][c].0.as_str()).collect::<Vec<&str>>().join(" "));
+        if result.len() >= n {
+            break;
+        }
+        for i in 0..choices.len() {
+            if choices[i] + 1 < candidates[i].len() {
+                let mut next = choices.clone();
+                next[i] += 1;
+                if seen.insert(next.clone()) {
+                    heap.push(Combination { log_probability: log_probability(&next), choices: next });
+                }
+            }
+        }
+    }
+    result
+}
+
 // From http://humanum.arts.cuhk.edu.hk/Lexis/Canton2/syllabary/ , revised manually with
 // suggestions by Chaaak (updates from LSHK), and some are our own modifications (IIRC).
 // Some combinations do not make sense, but these functions are not supposed to validate the
 // pronunciations, but rather to just validate the format.
-const JYUTPING_CONSONANTS : &str = "(b|p|m|f|d|t|n|l|g|k|ng|h|gw|kw|w|z|c|s|j)";
-const JYUTPING_FINALS : &str = "(i|ip|it|ik|im|in|ing|iu|yu|yut|yun|u|up|ut|uk|um|un|ung|ui|e|ep|et|ek|em|en|eng|ei|eu|eot|eon|eoi|oe|oet|oek|oeng|o|ot|ok|on|ong|oi|ou|op|om|a|ap|at|ak|am|an|ang|ai|au|aa|aap|aat|aak|aam|aan|aang|aai|aau|m|ng)";
+pub(crate) const JYUTPING_CONSONANTS : &str = "(b|p|m|f|d|t|n|l|g|k|ng|h|gw|kw|w|z|c|s|j)";
+pub(crate) const JYUTPING_FINALS : &str = "(i|ip|it|ik|im|in|ing|iu|yu|yut|yun|u|up|ut|uk|um|un|ung|ui|e|ep|et|ek|em|en|eng|ei|eu|eot|eon|eoi|oe|oet|oek|oeng|o|ot|ok|on|ong|oi|ou|op|om|a|ap|at|ak|am|an|ang|ai|au|aa|aap|aat|aak|aam|aan|aang|aai|aau|m|ng)";
 const JYUTPING_TONES : &str = "[1-6]";
 
 /// Regex string for validating formatting of Jyutping. Does not try to determine whether the
@@ -71,3 +547,66 @@ pub fn jyutping_validator() -> &'static Regex {
 pub fn is_jyutping_valid(jyutping: &str) -> bool {
     jyutping_validator().is_match(jyutping)
 }
+
+/// Validates a Jyutping string strictly, i.e. the syllables must be actually used in Cantonese
+/// rather than just being well formatted. Returns a list of (syllable, reason) for each rejected
+/// syllable, which is empty if the string is valid.
+pub fn jyutping_rejections(jyutping: &str) -> Vec<(String, String)> {
+    jyutping::validate_jyutping(jyutping).into_iter().filter_map(|(s, res)| res.err().map(|e| (s, e.to_string()))).collect()
+}
+
+/// Validates a Jyutping string strictly. See jyutping_rejections for the reasons a string is
+/// rejected.
+pub fn is_jyutping_valid_strict(jyutping: &str) -> bool {
+    let validated = jyutping::validate_jyutping(jyutping);
+    !validated.is_empty() && validated.iter().all(|(_, res)| res.is_ok())
+}
+
+/// Regex for one or more Jyutping syllables written together without spaces (e.g. "gaa1ze1").
+fn jyutping_syllables_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(&format!("^(?:{}?{}{})+$", JYUTPING_CONSONANTS, JYUTPING_FINALS, JYUTPING_TONES)).unwrap() // XXX: unwrap error detectable immediately in tests
+    })
+}
+
+/// Regex for exception tokens (e.g. "!T", "!bas4"), which words.hk uses to mark parts of a
+/// pronunciation that cannot be written in Jyutping.
+fn jyutping_exception_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new("^![A-Za-z][A-Za-z0-9]*$").unwrap() // XXX: unwrap error detectable immediately in tests
+    })
+}
+
+/// Splits a Jyutping string into syllables, also splitting syllables that are written together
+/// (e.g. "gaa1ze1" => ["gaa1", "ze1"]). If allow_exceptions is true, exception tokens starting with
+/// '!' (e.g. "!T") are kept as separate tokens. Returns None if any part of the string is not
+/// valid Jyutping.
+pub fn tokenize_jyutping(s: &str, allow_exceptions: bool) -> Option<Vec<String>> {
+    let mut result = vec![];
+    for token in s.split_whitespace() {
+        if allow_exceptions && jyutping_exception_re().is_match(token) {
+            result.push(token.to_string());
+        } else if jyutping_syllables_re().is_match(token) {
+            // Each syllable ends with exactly one tone digit, so we can split after the digits
+            let mut syllable = String::new();
+            for c in token.chars() {
+                syllable.push(c);
+                if c.is_ascii_digit() {
+                    result.push(std::mem::take(&mut syllable));
+                }
+            }
+        } else {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+/// Canonicalizes the formatting of a Jyutping string, so that syllables are separated by exactly
+/// one space (e.g. " gaa1ze1 " => "gaa1 ze1"). See tokenize_jyutping for allow_exceptions. Returns
+/// None if the string is not valid Jyutping.
+pub fn canonicalize_jyutping(s: &str, allow_exceptions: bool) -> Option<String> {
+    tokenize_jyutping(s, allow_exceptions).map(|tokens| tokens.join(" "))
+}
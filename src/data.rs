@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::BufRead;
 use std::sync::OnceLock;
+use crate::common;
 
 pub enum DataKind {
     CantoneseCharListWithJyutping,
@@ -9,6 +10,9 @@ pub enum DataKind {
     RadicalLabelToChars,
     UnihanData,
     EnglishVariants,
+    WordshkCharset,
+    WordshkVariantMap,
+    WordshkAutoconvert,
 }
 
 /// Set or get the path to the data file. Then, read the file and load the data (if applicable).
@@ -19,6 +23,9 @@ pub fn initialize_data(which : DataKind, path: &str) {
         DataKind::RadicalLabelToChars => { _radical_label_to_chars(Some(path), None, false); },
         DataKind::UnihanData => { _unihan_data(Some(path)); },
         DataKind::EnglishVariants => { _english_variants_data(Some(path), None, false); },
+        DataKind::WordshkCharset => { _wordshk_charset(Some(path), None, false); },
+        DataKind::WordshkVariantMap => { _wordshk_variantmap(Some(path), None, false); },
+        DataKind::WordshkAutoconvert => { _wordshk_autoconvert(Some(path), None, false); },
     }
 }
 
@@ -359,3 +366,137 @@ fn _english_variants_data(path:Option<&str>, data_str: Option<&str>, load: bool)
         DATA.get_or_init(|| { HashMap::new() })
     }
 }
+
+/// The set of characters words.hk considers to be "canonical" character forms.
+pub fn wordshk_charset() -> &'static HashSet<char> {
+#[cfg(feature = "downloaded_data")]
+    return _wordshk_charset(None, Some(include_str!("../lists/wordshk_charset.json")), true);
+#[cfg(not(feature = "downloaded_data"))]
+    return _wordshk_charset(None, None, true);
+}
+
+fn _wordshk_charset(path:Option<&str>, data_str: Option<&str>, load: bool) -> &'static HashSet<char> {
+    // Only the first path is kept, since the data is only loaded once anyway
+    static PATH: OnceLock<String> = OnceLock::new();
+    if let Some(path) = path {
+        let _ = PATH.set(path.to_string());
+    }
+    if load {
+        static DATA: OnceLock<HashSet<char>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            if let Some(data_str) = data_str {
+                serde_json::from_str(data_str).expect("Failed to parse data_str")
+            } else {
+                let path = PATH.get().expect("Please initialize the data path first");
+                let file = File::open(path).unwrap_or_else(|e| panic!("Failed to open file {:?}: {}", path, e));
+                let reader = std::io::BufReader::new(file);
+                serde_json::from_reader(reader).expect("Failed to read/parse data from file")
+            }
+        })
+    } else {
+        static DATA: OnceLock<HashSet<char>> = OnceLock::new();
+        DATA.get_or_init(|| { HashSet::new() })
+    }
+}
+
+/// Builds the words.hk canonical character set from the source lists (see edb_charlist.txt and
+/// wordshk_charlist.txt in lists/). Each line adds its CJK characters to the set, except lines
+/// starting with '!', which remove them instead. The files are processed in order, so later files
+/// can patch the earlier ones. Use `wordshk_charset` for the pre-generated version of the set.
+pub fn build_wordshk_charset(paths: &[&str]) -> HashSet<char> {
+    let mut resultset = HashSet::new();
+    for f in paths {
+        let file = File::open(f).unwrap_or_else(|e| panic!("File {} not found: {}", f, e));
+        for line in std::io::BufReader::new(file).lines() {
+            let line = line.unwrap_or_else(|e| panic!("Error reading file {}: {}", f, e));
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('!') {
+                for c in line.chars().skip(1) {
+                    if common::is_cjk_cp(c as u32) {
+                        resultset.remove(&c);
+                    }
+                }
+            } else {
+                for c in line.chars() {
+                    if common::is_cjk_cp(c as u32) {
+                        resultset.insert(c);
+                    }
+                }
+            }
+        }
+    }
+    resultset
+}
+
+/// A map of (variant character) => (words.hk canonical character), as generated by `zigen
+/// generate_wordshk_variantmap`. Note that some of the variants are themselves in the canonical
+/// set (e.g. 著 => 着), so blindly applying this map can be lossy.
+pub fn wordshk_variantmap() -> &'static HashMap<char, char> {
+#[cfg(feature = "downloaded_data")]
+    return _wordshk_variantmap(None, Some(include_str!("../lists/wordshk_variantmap.json")), true);
+#[cfg(not(feature = "downloaded_data"))]
+    return _wordshk_variantmap(None, None, true);
+}
+
+fn _wordshk_variantmap(path:Option<&str>, data_str: Option<&str>, load: bool) -> &'static HashMap<char, char> {
+    // Only the first path is kept, since the data is only loaded once anyway
+    static PATH: OnceLock<String> = OnceLock::new();
+    if let Some(path) = path {
+        let _ = PATH.set(path.to_string());
+    }
+    if load {
+        static DATA: OnceLock<HashMap<char, char>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            if let Some(data_str) = data_str {
+                serde_json::from_str(data_str).expect("Failed to parse data_str")
+            } else {
+                let path = PATH.get().expect("Please initialize the data path first");
+                let file = File::open(path).unwrap_or_else(|e| panic!("Failed to open file {:?}: {}", path, e));
+                let reader = std::io::BufReader::new(file);
+                serde_json::from_reader(reader).expect("Failed to read/parse data from file")
+            }
+        })
+    } else {
+        static DATA: OnceLock<HashMap<char, char>> = OnceLock::new();
+        DATA.get_or_init(|| { HashMap::new() })
+    }
+}
+
+/// The "safe" (not-so-controversial) subset of the variant map. words.hk automatically converts
+/// these characters to the canonical form.
+pub fn wordshk_autoconvert() -> &'static HashMap<char, char> {
+#[cfg(feature = "downloaded_data")]
+    return _wordshk_autoconvert(None, Some(include_str!("../lists/wordshk_autoconvert.json")), true);
+#[cfg(not(feature = "downloaded_data"))]
+    return _wordshk_autoconvert(None, None, true);
+}
+
+fn _wordshk_autoconvert(path:Option<&str>, data_str: Option<&str>, load: bool) -> &'static HashMap<char, char> {
+    // Only the first path is kept, since the data is only loaded once anyway
+    static PATH: OnceLock<String> = OnceLock::new();
+    if let Some(path) = path {
+        let _ = PATH.set(path.to_string());
+    }
+    if load {
+        static DATA: OnceLock<HashMap<char, char>> = OnceLock::new();
+        DATA.get_or_init(|| {
+            if let Some(data_str) = data_str {
+                serde_json::from_str(data_str).expect("Failed to parse data_str")
+            } else {
+                let path = PATH.get().expect("Please initialize the data path first");
+                let file = File::open(path).unwrap_or_else(|e| panic!("Failed to open file {:?}: {}", path, e));
+                let reader = std::io::BufReader::new(file);
+                serde_json::from_reader(reader).expect("Failed to read/parse data from file")
+            }
+        })
+    } else {
+        static DATA: OnceLock<HashMap<char, char>> = OnceLock::new();
+        DATA.get_or_init(|| { HashMap::new() })
+    }
+}
@@ -0,0 +1,207 @@
+/*!
+Discovery of new words. A corpus is segmented with a dictionary, and the strings of CJK characters
+that the dictionary has no words for are scored by how often they occur, how strongly their
+characters stick together (cohesion) and how freely they combine with their neighbours (branching
+entropy), so that the strings that are likely words come first. This helps find words to add to
+wordlist or to a user dictionary (see user_dictionary).
+*/
+
+use std::collections::HashMap;
+use crate::{common, data, segmentation};
+
+/// Options of WordDiscoverer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveryOptions {
+    /// The length of the shortest candidate, in characters. Single characters can't be split to
+    /// get their cohesion, so candidates have at least 2 characters even if this is lower.
+    pub min_len: usize,
+    /// The length of the longest candidate, in characters.
+    pub max_len: usize,
+    /// Candidates that occur fewer times than this are dropped.
+    pub min_count: u64,
+    /// Candidates with a lower cohesion (see Candidate) are dropped.
+    pub min_cohesion: f64,
+    /// Candidates with a lower left or right branching entropy (see Candidate) are dropped.
+    pub min_entropy: f64,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions { min_len: 2, max_len: 4, min_count: 3, min_cohesion: 1.0, min_entropy: 0.5 }
+    }
+}
+
+/// A candidate new word found by WordDiscoverer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The text of the candidate.
+    pub text: String,
+    /// The number of times the candidate occurs where the dictionary has no word for it.
+    pub count: u64,
+    /// The pointwise mutual information of the weakest split of the candidate into two parts, in
+    /// nats. High cohesion means that the parts occur together much more often than by chance.
+    pub cohesion: f64,
+    /// The entropy of the characters before the candidate, in nats. A word can follow many
+    /// different characters, while a fragment of a longer word is always preceded by the rest.
+    pub left_entropy: f64,
+    /// The entropy of the characters after the candidate, in nats.
+    pub right_entropy: f64,
+    /// The overall score, which is ln(count) + cohesion + min(left_entropy, right_entropy). Higher
+    /// is better.
+    pub score: f64,
+}
+
+/// Statistics of the neighbours of a candidate. The start and the end of a stretch of CJK
+/// characters count as a different neighbour every time, since they don't restrict what a word
+/// can be next to.
+#[derive(Default)]
+struct Neighbours {
+    chars: HashMap<char, u64>,
+    boundaries: u64,
+}
+
+impl Neighbours {
+    fn add(&mut self, ch: Option<char>) {
+        match ch {
+            Some(ch) => *self.chars.entry(ch).or_insert(0) += 1,
+            None => self.boundaries += 1,
+        }
+    }
+
+    fn entropy(&self) -> f64 {
+        let total = (self.chars.values().sum::<u64>() + self.boundaries) as f64;
+        let term = |count: u64| {
+            let p = count as f64 / total;
+            -p * p.ln()
+        };
+        self.chars.values().map(|&count| term(count)).sum::<f64>() + self.boundaries as f64 * term(1)
+    }
+}
+
+#[derive(Default)]
+struct CandidateStats {
+    count: u64,
+    left: Neighbours,
+    right: Neighbours,
+}
+
+/// Finds candidate new words (out-of-vocabulary words) in a corpus. Every line is segmented with
+/// the dictionary, the same way as segment_with_dictionary, and the candidates are the strings in
+/// runs of odd characters and single-character segments, which is how words that the dictionary
+/// doesn't know end up. Candidates are scored by their frequency, cohesion and branching entropy
+/// (see Candidate).
+pub struct WordDiscoverer {
+    segmenter: segmentation::Segmenter,
+    options: DiscoveryOptions,
+    /// The number of times each string of up to max_len CJK characters occurs in the corpus
+    ngram_counts: HashMap<String, u64>,
+    /// The number of CJK characters in the corpus
+    total_chars: u64,
+    candidates: HashMap<String, CandidateStats>,
+}
+
+impl WordDiscoverer {
+    /// Creates a discoverer with the words of wordlist as the dictionary.
+    pub fn new(options: DiscoveryOptions) -> Self {
+        let wordlist = data::cantonese_wordlist_with_jyutping();
+        Self::with_dictionary(wordlist.keys().map(|w| w.as_str()), options)
+    }
+
+    /// Creates a discoverer with the words as the dictionary.
+    pub fn with_dictionary<'a, I: IntoIterator<Item = &'a str>>(dictionary: I, options: DiscoveryOptions) -> Self {
+        WordDiscoverer {
+            segmenter: segmentation::Segmenter::from_dictionary(dictionary),
+            options,
+            ngram_counts: HashMap::new(),
+            total_chars: 0,
+            candidates: HashMap::new(),
+        }
+    }
+
+    /// Adds a line of the corpus.
+    pub fn add_line(&mut self, line: &str) {
+        let chars : Vec<char> = line.chars().collect();
+        let is_cjk = |i: usize| common::is_cjk_cp(chars[i] as u32);
+        let (min_len, max_len) = (self.options.min_len.max(2), self.options.max_len);
+
+        for i in (0..chars.len()).filter(|&i| is_cjk(i)) {
+            self.total_chars += 1;
+            for end in (i + 1..=(i + max_len).min(chars.len())).take_while(|&end| is_cjk(end - 1)) {
+                *self.ngram_counts.entry(chars[i..end].iter().collect()).or_insert(0) += 1;
+            }
+        }
+
+        // The runs of odd characters and single-character segments
+        let (_, segments) = self.segmenter.segment(line);
+        let ends = segments.iter().skip(1).copied().chain(std::iter::once(chars.len()));
+        let mut runs : Vec<(usize, usize)> = vec![];
+        for (start, end) in segments.iter().copied().zip(ends) {
+            if end - start > 1 || !is_cjk(start) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(run) if run.1 == start => run.1 = end,
+                _ => runs.push((start, end)),
+            }
+        }
+
+        let neighbour = |i: Option<usize>| i.filter(|&i| i < chars.len() && is_cjk(i)).map(|i| chars[i]);
+        for (run_start, run_end) in runs {
+            for start in run_start..run_end {
+                for end in (start + min_len)..=(start + max_len).min(run_end) {
+                    let stats = self.candidates.entry(chars[start..end].iter().collect()).or_default();
+                    stats.count += 1;
+                    stats.left.add(neighbour(start.checked_sub(1)));
+                    stats.right.add(neighbour(Some(end)));
+                }
+            }
+        }
+    }
+
+    /// Adds every line of the text.
+    pub fn add_text(&mut self, text: &str) {
+        for line in text.lines() {
+            self.add_line(line);
+        }
+    }
+
+    /// The cohesion of the word (see Candidate), which has at least 2 characters.
+    fn cohesion(&self, chars: &[char]) -> f64 {
+        let probability = |chars: &[char]| {
+            let s : String = chars.iter().collect();
+            self.ngram_counts.get(&s).copied().unwrap_or(0) as f64 / self.total_chars as f64
+        };
+        let p = probability(chars);
+        (1..chars.len())
+            .map(|i| (p / (probability(&chars[..i]) * probability(&chars[i..]))).ln())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Gets the candidates that pass the thresholds of the options, from the best to the worst.
+    pub fn candidates(&self) -> Vec<Candidate> {
+        let options = &self.options;
+        let mut result : Vec<Candidate> = self.candidates.iter()
+            .filter(|(_, stats)| stats.count >= options.min_count)
+            .map(|(text, stats)| {
+                let chars : Vec<char> = text.chars().collect();
+                let cohesion = self.cohesion(&chars);
+                let (left_entropy, right_entropy) = (stats.left.entropy(), stats.right.entropy());
+                let score = (stats.count as f64).ln() + cohesion + left_entropy.min(right_entropy);
+                Candidate { text: text.clone(), count: stats.count, cohesion, left_entropy, right_entropy, score }
+            })
+            .filter(|c| c.cohesion >= options.min_cohesion && c.left_entropy.min(c.right_entropy) >= options.min_entropy)
+            .collect();
+        result.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.text.cmp(&b.text)));
+        result
+    }
+}
+
+/// Finds candidate new words in the lines of the corpus with the words of wordlist as the
+/// dictionary. See WordDiscoverer.
+pub fn discover_words<'a, I: IntoIterator<Item = &'a str>>(lines: I, options: DiscoveryOptions) -> Vec<Candidate> {
+    let mut discoverer = WordDiscoverer::new(options);
+    for line in lines {
+        discoverer.add_line(line);
+    }
+    discoverer.candidates()
+}
@@ -0,0 +1,300 @@
+/*!
+A structured representation of Jyutping syllables. The inventory of initials and finals is the
+same as the one used by the validators in the cantonese module.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::cantonese;
+
+/// Splits a regex alternation like "(a|b|c)" into its parts.
+fn split_alternation(s: &'static str) -> Vec<&'static str> {
+    s.trim_start_matches('(').trim_end_matches(')').split('|').collect()
+}
+
+/// All the Jyutping initials (excluding the null initial).
+pub fn initials() -> &'static [&'static str] {
+    static DATA: OnceLock<Vec<&'static str>> = OnceLock::new();
+    DATA.get_or_init(|| split_alternation(cantonese::JYUTPING_CONSONANTS))
+}
+
+/// All the Jyutping finals.
+pub fn finals() -> &'static [&'static str] {
+    static DATA: OnceLock<Vec<&'static str>> = OnceLock::new();
+    DATA.get_or_init(|| split_alternation(cantonese::JYUTPING_FINALS))
+}
+
+// The nuclei of the finals, longest first so that "aa" is matched before "a". The syllabic nasals
+// m and ng are their own nuclei.
+const NUCLEI : [&str; 11] = ["aa", "oe", "eo", "yu", "a", "e", "i", "o", "u", "m", "ng"];
+
+/// Reasons for a string not being a Jyutping syllable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseSyllableError {
+    /// The syllable doesn't end with a tone number (1-6)
+    MissingTone,
+    /// The consonants before the final are not a Jyutping initial
+    UnknownInitial(String),
+    /// The final is not a Jyutping final
+    UnknownFinal(String),
+    /// The initial and the final are both valid, but they are not used together in Cantonese
+    ImpossibleCombination(String, String),
+    /// The final ends with p, t or k, but the tone is not one of the entering tones (1, 3, 6)
+    CheckedFinalTone(u8),
+}
+
+impl fmt::Display for ParseSyllableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseSyllableError::MissingTone => write!(f, "missing tone"),
+            ParseSyllableError::UnknownInitial(s) => write!(f, "unknown initial: {}", s),
+            ParseSyllableError::UnknownFinal(s) => write!(f, "unknown final: {}", s),
+            ParseSyllableError::ImpossibleCombination(i, fi) => write!(f, "impossible combination: {}+{}", if i.is_empty() { "0" } else { i }, fi),
+            ParseSyllableError::CheckedFinalTone(t) => write!(f, "checked final with non-entering tone: {}", t),
+        }
+    }
+}
+
+impl std::error::Error for ParseSyllableError {}
+
+/// A Jyutping syllable, e.g. "gwong2" is the initial "gw", the final "ong" and tone 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Syllable {
+    initial: &'static str,
+    final_: &'static str,
+    tone: u8,
+}
+
+impl Syllable {
+    /// Creates a syllable from its parts. Use "" for the null initial. Returns None if the parts are
+    /// not in the Jyutping inventory or the tone is not 1-6. This does not check whether the
+    /// combination is actually used in Cantonese.
+    pub fn new(initial: &str, final_: &str, tone: u8) -> Option<Syllable> {
+        let initial = if initial.is_empty() { "" } else { *initials().iter().find(|i| **i == initial)? };
+        let final_ = *finals().iter().find(|f| **f == final_)?;
+        if !(1..=6).contains(&tone) {
+            return None;
+        }
+        Some(Syllable { initial, final_, tone })
+    }
+
+    /// The initial, or "" for the null initial.
+    pub fn initial(&self) -> &'static str {
+        self.initial
+    }
+
+    /// The final, i.e. everything after the initial excluding the tone.
+    pub fn final_(&self) -> &'static str {
+        self.final_
+    }
+
+    /// The tone number (1-6).
+    pub fn tone(&self) -> u8 {
+        self.tone
+    }
+
+    /// The vowel (or syllabic nasal) of the final, e.g. "eo" for "eoi".
+    pub fn nucleus(&self) -> &'static str {
+        let nucleus = NUCLEI.iter().find(|n| self.final_.starts_with(**n)).expect("all finals start with a nucleus");
+        &self.final_[..nucleus.len()]
+    }
+
+    /// The ending of the final after the nucleus, e.g. "i" for "eoi". Returns "" if there is none.
+    pub fn coda(&self) -> &'static str {
+        &self.final_[self.nucleus().len()..]
+    }
+
+    /// Whether the final ends with a stop (p, t, k). These are the "entering tone" (入聲) syllables.
+    pub fn is_checked(&self) -> bool {
+        matches!(self.coda(), "p" | "t" | "k")
+    }
+
+    /// The syllable without the tone, e.g. "gwong" for "gwong2".
+    pub fn toneless(&self) -> String {
+        format!("{}{}", self.initial, self.final_)
+    }
+
+    /// Whether the combination of the initial and the final is attested in Cantonese, according to
+    /// syllable_inventory.
+    pub fn is_attested(&self) -> bool {
+        syllable_inventory().get(self.final_).is_some_and(|initials| initials.contains(&self.initial))
+    }
+
+    /// The same syllable with another tone. Returns None if the tone is not 1-6.
+    pub fn with_tone(&self, tone: u8) -> Option<Syllable> {
+        Syllable::new(self.initial, self.final_, tone)
+    }
+
+    /// Whether this syllable is a changed tone (變調) of the other syllable, i.e. they only differ in
+    /// the tone and the tone change is one of those in is_changed_tone (e.g. "mui2" for "mui6").
+    pub fn is_changed_tone_of(&self, base: &Syllable) -> bool {
+        self.initial == base.initial && self.final_ == base.final_ && is_changed_tone(base.tone, self.tone)
+    }
+}
+
+impl FromStr for Syllable {
+    type Err = ParseSyllableError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tone = s.chars().last().and_then(|c| c.to_digit(10)).filter(|t| (1..=6).contains(t)).ok_or(ParseSyllableError::MissingTone)? as u8;
+        let toneless = &s[..s.len() - 1];
+
+        // The initial is everything before the first vowel. Without vowels, the final must be one
+        // of the syllabic nasals (e.g. "m4", "ng5", "hm4").
+        let split = match toneless.find(['a', 'e', 'i', 'o', 'u', 'y']) {
+            Some(idx) => idx,
+            None if toneless.ends_with("ng") => toneless.len() - 2,
+            None if toneless.ends_with('m') => toneless.len() - 1,
+            None => return Err(ParseSyllableError::UnknownFinal(toneless.to_string())),
+        };
+        let (initial, final_) = toneless.split_at(split);
+
+        let initial = if initial.is_empty() {
+            ""
+        } else {
+            *initials().iter().find(|i| **i == initial).ok_or_else(|| ParseSyllableError::UnknownInitial(initial.to_string()))?
+        };
+        let final_ = *finals().iter().find(|f| **f == final_).ok_or_else(|| ParseSyllableError::UnknownFinal(final_.to_string()))?;
+        Ok(Syllable { initial, final_, tone })
+    }
+}
+
+impl fmt::Display for Syllable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}{}", self.initial, self.final_, self.tone)
+    }
+}
+
+// Initial+final combinations that are attested in the (CJK) characters of charlist.json, which
+// includes colloquial syllables such as "deng6" and "ngaak1". Keyed by final, "0" is the null
+// initial. Finals that are never used with any initial (e.g. "up", "om") are left out.
+const SYLLABLE_INVENTORY : [(&str, &str); 60] = [
+    ("i", "b m d t n l g ng h w z c s j"),
+    ("ip", "b d t n l g h z c s j"),
+    ("it", "b p m f d t l g k h z c s j"),
+    ("ik", "0 b p m f d t n l g k kw w z c s j"),
+    ("im", "d t n l g k h z c s j"),
+    ("in", "b p m d t n l g k h z c s j"),
+    ("ing", "b p m f d t n l g k h gw w z c s j"),
+    ("iu", "b p m f d t n l g k h z c s j"),
+    ("yu", "z c s j"),
+    ("yut", "d t l g k h z c s j"),
+    ("yun", "d t n l g k h z c s j"),
+    ("u", "b m f t l g k w"),
+    ("ut", "b p m f d g k w"),
+    ("uk", "0 b p m f d t l g k h w z c s j"),
+    ("un", "b p m f g w"),
+    ("ung", "0 b p m f d t n l g k ng h z c s j"),
+    ("ui", "b p m f t g k w"),
+    ("e", "0 b p m f d n l g k ng h gw w z c s j"),
+    ("ep", "d g k z s"),
+    ("et", "0 b p d t k ng z c"),
+    ("ek", "0 b p d t l k ng h gw z c s"),
+    ("em", "l k z"),
+    ("en", "j"),
+    ("eng", "b p m d t l g k h z c s j"),
+    ("ei", "b p m f d n l g k h w s"),
+    ("eu", "p m d l k z"),
+    ("eot", "n l z c s"),
+    ("eon", "d t l z c s j"),
+    ("eoi", "d t n l g k h z c s j"),
+    ("oe", "d t l g k h z s"),
+    ("oet", "0 c"),
+    ("oek", "0 d l g k z c s j"),
+    ("oeng", "d n l g k h z c s j"),
+    ("o", "0 b p m f d t n l g k ng h gw w z c s j"),
+    ("ot", "p g h"),
+    ("ok", "0 b p m f d t n l g k ng h gw kw w z c s"),
+    ("on", "0 m g ng h"),
+    ("ong", "0 b p m f d t n l g k ng h gw kw w z c s"),
+    ("oi", "0 d t n l g k ng h z c s"),
+    ("ou", "0 b p m d t n l g ng h w z c s"),
+    ("op", "c"),
+    ("ap", "0 d t n l g k ng h z c s j"),
+    ("at", "0 b p m f d l g k ng h gw w z c s j"),
+    ("ak", "0 b m d n l k ng h gw z c s"),
+    ("am", "0 b m d t n l g k ng h z c s j"),
+    ("an", "0 b p m f d t n l g k ng h gw kw w z c s j"),
+    ("ang", "0 b p m f d t n l g k ng h gw kw w z c s j"),
+    ("ai", "0 b p m f d t n l g k ng h gw kw w z c s j"),
+    ("au", "0 b p m f d t n l g k ng h z c s j"),
+    ("aa", "0 b p m f d t n l g k ng h gw kw w z c s j"),
+    ("aap", "0 d t n l g h z c s j"),
+    ("aat", "0 b p m f d t n l g k ng h gw w z c s"),
+    ("aak", "0 b p m f d t l g k ng h gw kw w z c s j"),
+    ("aam", "0 d t n l g ng h z c s j"),
+    ("aan", "0 b p m f d t n l g k ng h gw w z c s"),
+    ("aang", "0 b p m l g ng h gw kw w z c s j"),
+    ("aai", "0 b p m f d t n l g k ng h gw kw w z c s j"),
+    ("aau", "0 b p m n l g k ng h z c s j"),
+    ("m", "0 h"),
+    ("ng", "0 h"),
+];
+
+/// Whether a syllable with the tone `base` can be pronounced with the tone `changed` as a changed
+/// tone (變調). Cantonese has two changed tones: the high rising 35 pin-jam (written as tone 2),
+/// which can replace tones 3, 4, 5 and 6 (e.g. 妹 mui6 => mui2), and the high level 55 pin-jam
+/// (written as tone 1), which can replace tones 3, 4 and 6 (e.g. 姨 ji4 => ji1).
+pub fn is_changed_tone(base: u8, changed: u8) -> bool {
+    match changed {
+        2 => matches!(base, 3..=6),
+        1 => matches!(base, 3 | 4 | 6),
+        _ => false,
+    }
+}
+
+/// Map of (final) => (initials that can be used with the final). The null initial is "".
+pub fn syllable_inventory() -> &'static HashMap<&'static str, Vec<&'static str>> {
+    static DATA: OnceLock<HashMap<&'static str, Vec<&'static str>>> = OnceLock::new();
+    DATA.get_or_init(|| {
+        SYLLABLE_INVENTORY.iter()
+            .map(|(f, is)| (*f, is.split(' ').map(|i| if i == "0" { "" } else { i }).collect()))
+            .collect()
+    })
+}
+
+/// Parses a syllable and checks that it is phonotactically valid, i.e. unlike parsing with
+/// FromStr, the initial+final combination must be attested (see syllable_inventory) and checked
+/// finals must have an entering tone. Note that changed tones on checked finals (e.g. "dip2" for
+/// 碟) are rejected too, since they cannot be told apart from typos.
+pub fn validate_syllable(s: &str) -> Result<Syllable, ParseSyllableError> {
+    let syllable : Syllable = s.parse()?;
+    if !syllable.is_attested() {
+        return Err(ParseSyllableError::ImpossibleCombination(syllable.initial.to_string(), syllable.final_.to_string()));
+    }
+    if syllable.is_checked() && !matches!(syllable.tone, 1 | 3 | 6) {
+        return Err(ParseSyllableError::CheckedFinalTone(syllable.tone));
+    }
+    Ok(syllable)
+}
+
+/// Validates every syllable of a Jyutping string with validate_syllable. Syllables written
+/// together are split after the tone numbers, so trailing garbage (e.g. "si1xyz") is reported as a
+/// separate syllable. Returns each syllable with the result of the validation.
+pub fn validate_jyutping(s: &str) -> Vec<(String, Result<Syllable, ParseSyllableError>)> {
+    let mut result = vec![];
+    for token in s.split_whitespace() {
+        let mut syllable = String::new();
+        for c in token.chars() {
+            syllable.push(c);
+            if c.is_ascii_digit() {
+                let res = validate_syllable(&syllable);
+                result.push((std::mem::take(&mut syllable), res));
+            }
+        }
+        if !syllable.is_empty() {
+            let res = validate_syllable(&syllable);
+            result.push((syllable, res));
+        }
+    }
+    result
+}
+
+/// Parses a Jyutping string into syllables. Syllables written together are split as in
+/// cantonese::tokenize_jyutping. Returns None if any part of the string is not valid Jyutping
+/// (exception tokens are not allowed).
+pub fn parse_jyutping(s: &str) -> Option<Vec<Syllable>> {
+    cantonese::tokenize_jyutping(s, false)?.iter().map(|t| t.parse().ok()).collect()
+}
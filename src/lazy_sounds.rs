@@ -0,0 +1,250 @@
+/*!
+Hong Kong "lazy sounds" (懶音), i.e. common mergers in casual speech such as n => l (nei5 => lei5)
+or dropping the initial ng (ngo5 => o5). Includes fuzzy Jyutping search that tolerates them, and
+the detection of lazy sounds in pronunciations.
+*/
+
+use crate::{cantonese, data};
+use crate::jyutping::{self, Syllable};
+use crate::reverse_lookup;
+use std::fmt;
+use std::str::FromStr;
+
+/// A merger of two sounds that are often confused, by native speakers and learners alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MergeRule {
+    /// The initials n and l, e.g. nei5 / lei5 ("n/l")
+    NL,
+    /// The initial ng and the null initial, e.g. ngo5 / o5 ("ng-")
+    NgInitial,
+    /// The initials gw and g (and kw and k), e.g. gwong2 / gong2 ("gw/g")
+    GwG,
+    /// The codas k and t, e.g. sik1 / sit1 ("-k/-t")
+    KT,
+    /// The codas ng and n, e.g. hang4 / han4 ("-ng/-n")
+    NgN,
+    /// The syllabic nasals ng and m, e.g. ng5 / m5 ("ng/m")
+    SyllabicNgM,
+    /// Missing tones, e.g. nei / nei5 ("tone")
+    ToneOmission,
+}
+
+/// All the merge rules.
+pub const ALL_MERGE_RULES : [MergeRule; 7] = [
+    MergeRule::NL,
+    MergeRule::NgInitial,
+    MergeRule::GwG,
+    MergeRule::KT,
+    MergeRule::NgN,
+    MergeRule::SyllabicNgM,
+    MergeRule::ToneOmission,
+];
+
+impl MergeRule {
+    /// The short name of the rule, e.g. "n/l".
+    pub fn name(&self) -> &'static str {
+        match self {
+            MergeRule::NL => "n/l",
+            MergeRule::NgInitial => "ng-",
+            MergeRule::GwG => "gw/g",
+            MergeRule::KT => "-k/-t",
+            MergeRule::NgN => "-ng/-n",
+            MergeRule::SyllabicNgM => "ng/m",
+            MergeRule::ToneOmission => "tone",
+        }
+    }
+}
+
+impl fmt::Display for MergeRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for MergeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL_MERGE_RULES.iter().find(|r| r.name() == s).copied().ok_or_else(|| format!("unknown merge rule: {}", s))
+    }
+}
+
+/// Whether the two initials are the same under the rule.
+fn initials_merge(rule: MergeRule, a: &str, b: &str) -> bool {
+    let pair = |x: &str, y: &str| (a == x && b == y) || (a == y && b == x);
+    match rule {
+        MergeRule::NL => pair("n", "l"),
+        MergeRule::NgInitial => pair("ng", ""),
+        MergeRule::GwG => pair("gw", "g") || pair("kw", "k"),
+        _ => false,
+    }
+}
+
+/// Whether the two finals are the same under the rule.
+fn finals_merge(rule: MergeRule, a: &Syllable, b: &Syllable) -> bool {
+    let pair = |x: &str, y: &str| (a.coda() == x && b.coda() == y) || (a.coda() == y && b.coda() == x);
+    match rule {
+        MergeRule::KT => a.nucleus() == b.nucleus() && pair("k", "t"),
+        MergeRule::NgN => a.nucleus() == b.nucleus() && pair("ng", "n"),
+        MergeRule::SyllabicNgM => (a.final_() == "ng" && b.final_() == "m") || (a.final_() == "m" && b.final_() == "ng"),
+        _ => false,
+    }
+}
+
+/// The rules needed to make the query syllable match the syllable, or None if they can't be made
+/// to match with the rules. A query syllable without a tone (None) only matches with the
+/// ToneOmission rule.
+fn syllable_merges(query: &Syllable, query_tone: Option<u8>, syllable: &Syllable, rules: &[MergeRule]) -> Option<Vec<MergeRule>> {
+    let mut fired = vec![];
+    if query.initial() != syllable.initial() {
+        fired.push(*rules.iter().find(|r| initials_merge(**r, query.initial(), syllable.initial()))?);
+    }
+    if query.final_() != syllable.final_() {
+        fired.push(*rules.iter().find(|r| finals_merge(**r, query, syllable))?);
+    }
+    match query_tone {
+        Some(tone) if tone != syllable.tone() => return None,
+        None if !rules.contains(&MergeRule::ToneOmission) => return None,
+        _ => {},
+    }
+    Some(fired)
+}
+
+/// Parses a query syllable, which may be missing the tone.
+fn parse_query_syllable(s: &str) -> Option<(Syllable, Option<u8>)> {
+    let s = s.to_lowercase();
+    if s.ends_with(|c: char| c.is_ascii_digit()) {
+        let syllable : Syllable = s.parse().ok()?;
+        Some((syllable, Some(syllable.tone())))
+    } else {
+        Some((format!("{}1", s).parse().ok()?, None))
+    }
+}
+
+/// Searches wordlist for words whose pronunciation matches the Jyutping query when the sounds
+/// merged by the rules are considered the same, e.g. "lei5 hou2" finds 你好 (nei5 hou2) with the
+/// n/l rule. Syllables in the query can be written without tones if the ToneOmission rule is
+/// used. Returns (word, pronunciation, cost) sorted by the cost, which is the number of mergers
+/// needed to match (missing tones are free), and then by how common the word is. Returns an empty
+/// list if the query is not valid Jyutping.
+pub fn fuzzy_search(query: &str, rules: &[MergeRule]) -> Vec<(String, String, u32)> {
+    let Some(query) = query.split_whitespace().map(parse_query_syllable).collect::<Option<Vec<_>>>() else {
+        return vec![];
+    };
+    if query.is_empty() {
+        return vec![];
+    }
+
+    let mut results : Vec<(&str, &str, u32, u64)> = vec![];
+    for (word, pronunciation, syllables) in cantonese::parsed_wordlist() {
+        if syllables.len() != query.len() {
+            continue;
+        }
+        let cost = query.iter().zip(syllables)
+            .map(|((q, tone), s)| syllable_merges(q, *tone, s, rules).map(|fired| fired.len() as u32))
+            .sum::<Option<u32>>();
+        if let Some(cost) = cost {
+            results.push((word, pronunciation, cost, reverse_lookup::word_count(word, syllables)));
+        }
+    }
+    results.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| b.3.cmp(&a.3)).then_with(|| a.0.cmp(b.0)).then_with(|| a.1.cmp(b.1)));
+    results.into_iter().map(|(w, p, cost, _)| (w.to_string(), p.to_string(), cost)).collect()
+}
+
+/// Maximum number of results returned by careful_speech_candidates.
+pub const CAREFUL_CANDIDATES_MAX : usize = 16;
+
+/// The careful speech forms that the syllable could be a lazy sound of, with the rule that maps
+/// them to the syllable (e.g. "nei5" with the n/l rule for "lei5"). Only forms that are attested
+/// syllables (see Syllable::is_attested) are returned.
+pub fn careful_syllables(syllable: &Syllable) -> Vec<(Syllable, MergeRule)> {
+    let (initial, final_, tone) = (syllable.initial(), syllable.final_(), syllable.tone());
+    let nucleus = syllable.nucleus();
+    let candidates = [
+        (initial == "l", "n", final_.to_string(), MergeRule::NL),
+        (initial.is_empty(), "ng", final_.to_string(), MergeRule::NgInitial),
+        (initial == "g", "gw", final_.to_string(), MergeRule::GwG),
+        (initial == "k", "kw", final_.to_string(), MergeRule::GwG),
+        (syllable.coda() == "t", initial, format!("{}k", nucleus), MergeRule::KT),
+        (syllable.coda() == "n", initial, format!("{}ng", nucleus), MergeRule::NgN),
+        (final_ == "m", initial, "ng".to_string(), MergeRule::SyllabicNgM),
+    ];
+    candidates.into_iter()
+        .filter(|(applies, _, _, _)| *applies)
+        .filter_map(|(_, i, f, rule)| Some((Syllable::new(i, &f, tone)?, rule)))
+        .filter(|(s, _)| s.is_attested())
+        .collect()
+}
+
+/// Gets the careful speech (i.e. not lazy sound) forms that a Jyutping string could stand for, with
+/// the rules that map them to the string, e.g. "ngo5 nei5" with [ng-, n/l] for "o5 lei5". The
+/// candidates with the fewest changes come first, and at most CAREFUL_CANDIDATES_MAX are returned.
+/// The string itself is not included. Returns an empty list if the string is not valid Jyutping.
+pub fn careful_speech_candidates(jyutping: &str) -> Vec<(String, Vec<MergeRule>)> {
+    let Some(syllables) = jyutping::parse_jyutping(jyutping) else {
+        return vec![];
+    };
+
+    // Extend the candidates one syllable at a time, keeping only the best ones. The number of
+    // changes only grows, so the candidates that are dropped can never become one of the best.
+    let mut candidates : Vec<(Vec<Syllable>, Vec<MergeRule>)> = vec![(vec![], vec![])];
+    for syllable in &syllables {
+        let options : Vec<(Syllable, Option<MergeRule>)> = std::iter::once((*syllable, None))
+            .chain(careful_syllables(syllable).into_iter().map(|(s, rule)| (s, Some(rule))))
+            .collect();
+        let mut next = vec![];
+        for (prefix, rules) in &candidates {
+            for (s, rule) in &options {
+                let mut prefix = prefix.clone();
+                prefix.push(*s);
+                let mut rules = rules.clone();
+                rules.extend(rule);
+                next.push((prefix, rules));
+            }
+        }
+        next.sort_by(|a, b| a.1.len().cmp(&b.1.len()).then_with(|| a.0.cmp(&b.0)));
+        next.truncate(CAREFUL_CANDIDATES_MAX + 1);
+        candidates = next;
+    }
+
+    candidates.into_iter()
+        .filter(|(_, rules)| !rules.is_empty())
+        .take(CAREFUL_CANDIDATES_MAX)
+        .map(|(s, rules)| (s.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" "), rules))
+        .collect()
+}
+
+/// Scans wordlist for pronunciations that look like lazy sounds. A syllable is suspected to be a
+/// lazy sound if one of its careful speech forms (see careful_syllables) is a more common reading
+/// of the character in charlist (e.g. 你 read as lei5, where nei5 is more common). Returns (word,
+/// pronunciation, careful speech pronunciation, rules) sorted by the word.
+pub fn find_lazy_sound_entries() -> Vec<(String, String, String, Vec<MergeRule>)> {
+    let charlist = data::cantonese_charlist_with_jyutping();
+    let mut results = vec![];
+    for (word, pronunciation, syllables) in cantonese::parsed_wordlist() {
+        if word.chars().count() != syllables.len() {
+            continue;
+        }
+        let mut careful = syllables.clone();
+        let mut rules = vec![];
+        for (i, (ch, syllable)) in word.chars().zip(syllables).enumerate() {
+            let Some(readings) = charlist.get(&ch) else {
+                continue;
+            };
+            let count = |s: &Syllable| readings.get(&s.to_string()).copied().unwrap_or(0);
+            let best = careful_syllables(syllable).into_iter()
+                .filter(|(s, _)| count(s) > count(syllable))
+                .max_by(|a, b| count(&a.0).cmp(&count(&b.0)).then_with(|| b.0.cmp(&a.0)));
+            if let Some((s, rule)) = best {
+                careful[i] = s;
+                rules.push(rule);
+            }
+        }
+        if !rules.is_empty() {
+            let careful = careful.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" ");
+            results.push((word.to_string(), pronunciation.to_string(), careful, rules));
+        }
+    }
+    results.sort();
+    results
+}
@@ -2,7 +2,15 @@ pub mod common;
 pub mod english;
 pub mod ruby_match;
 pub mod segmentation;
+pub mod discovery;
+pub mod user_dictionary;
 pub mod cantonese;
+pub mod jyutping;
+pub mod romanization;
+pub mod reverse_lookup;
+pub mod lazy_sounds;
+pub mod normalization;
 pub mod cjk;
 pub mod data;
+pub mod variants;
 
@@ -0,0 +1,347 @@
+/*!
+Text normalization before pronunciation lookup. Arabic numerals, years, dates, times, percentages,
+amounts of money and some common abbreviations are verbalized into the Cantonese words that are
+read out (e.g. "$1,500" => "一千五百蚊", "2024年3月15日" => "二零二四年三月十五日"), which can then
+be looked up in wordlist like any other text.
+*/
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use crate::segmentation;
+
+const DIGITS : [char; 10] = ['零', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// Characters after which 2 is read as 二 rather than 兩 (e.g. 二月 for February and 二樓 for the
+/// second floor, but 兩個 and 兩點).
+const ORDINAL_SUFFIXES : &str = "月日號号樓楼期級级";
+
+/// Abbreviations that are replaced when they are written as separate words.
+const ABBREVIATIONS : [(&str, &str); 10] = [
+    ("HK$", "港幣"),
+    ("HKD", "港幣"),
+    ("USD", "美金"),
+    ("RMB", "人民幣"),
+    ("vs.", "對"),
+    ("vs", "對"),
+    ("VS", "對"),
+    ("etc.", "等等"),
+    ("e.g.", "例如"),
+    ("i.e.", "即係"),
+];
+
+/// Reads a string of digits one by one, e.g. "2024" => "二零二四". Returns None if the string has
+/// anything other than ASCII digits.
+pub fn verbalize_digits(s: &str) -> Option<String> {
+    if s.is_empty() {
+        return None;
+    }
+    s.chars().map(|c| c.to_digit(10).map(|d| DIGITS[d as usize])).collect()
+}
+
+/// Reads a non-negative integer, e.g. 1500 => "一千五百" and 10010 => "一萬零一十". A leading 2 is
+/// read as 兩 before 百, 千, 萬 and 億 (e.g. "兩千"). Numbers of 10^16 and above are read digit by
+/// digit.
+pub fn verbalize_integer(n: u64) -> String {
+    const PLACES : [&str; 4] = ["千", "百", "十", ""];
+    const GROUPS : [&str; 4] = ["", "萬", "億", "兆"];
+    if n == 0 {
+        return "零".to_string();
+    }
+    if n >= 10_000_000_000_000_000 {
+        return verbalize_digits(&n.to_string()).unwrap();
+    }
+
+    let mut groups = vec![];
+    let mut rest = n;
+    while rest > 0 {
+        groups.push(rest % 10000);
+        rest /= 10000;
+    }
+
+    let mut result = String::new();
+    // Skipped zeros are read as a single 零 before the next digit, e.g. 一千零五
+    let mut zero = false;
+    for (g, group) in groups.iter().enumerate().rev() {
+        if *group == 0 {
+            zero = true;
+            continue;
+        }
+        let digits = [group / 1000, group / 100 % 10, group / 10 % 10, group % 10];
+        for (place, d) in digits.into_iter().enumerate() {
+            if d == 0 {
+                zero = true;
+                continue;
+            }
+            if zero && !result.is_empty() {
+                result.push('零');
+            }
+            zero = false;
+            // 10-19 are read without the 一, e.g. 十二
+            if !(result.is_empty() && d == 1 && place == 2) {
+                result.push(DIGITS[d as usize]);
+            }
+            result.push_str(PLACES[place]);
+        }
+        result.push_str(GROUPS[g]);
+        // Zeros at the end of a group are not read, e.g. 一千萬一千
+        zero = false;
+    }
+
+    if result.starts_with('二') && result.chars().nth(1).is_some_and(|c| "百千萬億兆".contains(c)) {
+        result.replace_range(.."二".len(), "兩");
+    }
+    result
+}
+
+/// Reads a number, which can have a minus sign, thousands separators and decimals, e.g.
+/// "-1,234.5" => "負一千二百三十四點五". Returns None if the string is not a number.
+pub fn verbalize_number(s: &str) -> Option<String> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (integer, decimals) = match s.split_once('.') {
+        Some((integer, decimals)) => (integer, Some(decimals)),
+        None => (s, None),
+    };
+    if !number_re().is_match(integer) {
+        return None;
+    }
+
+    let integer = integer.replace(',', "");
+    let mut result = if negative { "負".to_string() } else { String::new() };
+    match integer.parse::<u64>() {
+        // Leading zeros are read out, e.g. for "007"
+        Ok(n) if !(integer.len() > 1 && integer.starts_with('0')) => result.push_str(&verbalize_integer(n)),
+        _ => result.push_str(&verbalize_digits(&integer)?),
+    }
+    if let Some(decimals) = decimals {
+        result.push('點');
+        result.push_str(&verbalize_digits(decimals)?);
+    }
+    Some(result)
+}
+
+/// Regex for the integer part of a number, with or without thousands separators.
+fn number_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:[0-9]{1,3}(?:,[0-9]{3})+|[0-9]+)$").unwrap())
+}
+
+/// Regex for the expressions that are normalized, from the most specific to the least specific.
+/// Digits can be ASCII or fullwidth (see ascii_digits).
+fn normalization_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        let num = r"(?:\d{1,3}(?:,\d{3})+|\d+)(?:\.\d+)?";
+        let abbreviations = ABBREVIATIONS.iter().map(|(a, _)| regex::escape(a)).collect::<Vec<String>>().join("|");
+        Regex::new(&[
+            r"(?P<ymd>(?P<ymd_y>\d{4})-(?P<ymd_m>\d{1,2})-(?P<ymd_d>\d{1,2}))".to_string(),
+            r"(?P<dmy>(?P<dmy_d>\d{1,2})/(?P<dmy_m>\d{1,2})/(?P<dmy_y>\d{4}))".to_string(),
+            r"(?P<time>(?P<time_h>\d{1,2}):(?P<time_m>\d{2})(?:\s*(?P<time_ampm>[aApP])\.?[mM]\.?)?)".to_string(),
+            r"(?P<hour>(?P<hour_h>\d{1,2})(?P<hour_ampm>[aApP])\.?[mM]\.?)".to_string(),
+            r"(?P<year>(?P<year_y>\d{4})\s*年)".to_string(),
+            format!(r"(?P<money>(?P<currency>HK\$|US\$|\$|RMB|€|£)\s*(?P<amount>{})(?P<multiplier>[萬億])?)", num),
+            format!(r"(?P<percent>(?P<percent_n>{})\s*[%％])", num),
+            format!(r"(?P<unit>(?P<unit_n>{})(?P<unit_name>km|kg|cm|mm|ml|m|g|°C|℃))", num),
+            format!(r"(?P<number>-?{})", num),
+            format!(r"(?P<abbreviation>{})", abbreviations),
+        ].join("|").replace(r"\d", "[0-9０-９]")).unwrap()
+    })
+}
+
+/// Replaces fullwidth digits with ASCII digits, e.g. "２０２４" => "2024".
+fn ascii_digits(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(|c| ('０'..='９').contains(&c)) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(s.chars().map(|c| match c {
+        '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+        _ => c,
+    }).collect())
+}
+
+/// Reads a number that comes before the character next (if any), using 兩 for 2 unless next is
+/// one of ORDINAL_SUFFIXES.
+fn verbalize_number_before(s: &str, next: Option<char>) -> Option<String> {
+    if s == "2" && next.is_some_and(|c| !c.is_ascii() && !ORDINAL_SUFFIXES.contains(c)) {
+        return Some("兩".to_string());
+    }
+    verbalize_number(s)
+}
+
+/// Reads a date, e.g. (2024, 3, 15) => "二零二四年三月十五日". Returns None if it's not a valid
+/// date.
+fn verbalize_date(year: &str, month: &str, day: &str) -> Option<String> {
+    let (m, d) = (month.parse::<u32>().ok()?, day.parse::<u32>().ok()?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    Some(format!("{}年{}月{}日", verbalize_digits(year)?, verbalize_integer(m as u64), verbalize_integer(d as u64)))
+}
+
+/// Reads a time of the day, e.g. ("10", "30", Some("p")) => "下晝十點半". Returns None if it's not
+/// a valid time.
+fn verbalize_time(hour: &str, minute: &str, am_pm: Option<&str>) -> Option<String> {
+    let (h, m) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+    if h > 24 || m > 59 || (am_pm.is_some() && !(1..=12).contains(&h)) {
+        return None;
+    }
+    let mut result = match am_pm.map(|s| s.to_ascii_lowercase()) {
+        Some(s) if s == "a" => "上晝".to_string(),
+        Some(_) => "下晝".to_string(),
+        None => String::new(),
+    };
+    result.push_str(&verbalize_number_before(&h.to_string(), Some('點'))?);
+    result.push('點');
+    match m {
+        0 => {},
+        30 => result.push('半'),
+        1..=9 => result.push_str(&format!("零{}分", DIGITS[m as usize])),
+        _ => result.push_str(&format!("{}分", verbalize_integer(m as u64))),
+    }
+    Some(result)
+}
+
+/// Reads an amount of money, e.g. ("HK$", "12.5") => "十二蚊五毫". Dollars are read as 蚊, with
+/// 毫 and 仙 for the cents, and other currencies are read as the number followed by the name of the
+/// currency (e.g. "一百美金").
+fn verbalize_money(currency: &str, amount: &str, multiplier: Option<&str>) -> Option<String> {
+    let unit = match currency {
+        "HK$" | "$" => "蚊",
+        "US$" => "美金",
+        "RMB" => "人民幣",
+        "€" => "歐羅",
+        "£" => "英鎊",
+        _ => return None,
+    };
+    let (integer, cents) = amount.split_once('.').unwrap_or((amount, ""));
+    if unit != "蚊" || multiplier.is_some() || cents.len() > 2 {
+        return Some(format!("{}{}{}", verbalize_number(amount)?, multiplier.unwrap_or(""), unit));
+    }
+
+    let integer = integer.replace(',', "");
+    let cents : Vec<u32> = cents.chars().filter_map(|c| c.to_digit(10)).collect();
+    let mut result = String::new();
+    if integer.parse::<u64>().ok()? > 0 || cents.iter().all(|c| *c == 0) {
+        result.push_str(&verbalize_number_before(&integer, Some('蚊'))?);
+        result.push('蚊');
+    }
+    for (digit, unit) in cents.iter().zip(['毫', '仙']) {
+        if *digit > 0 {
+            result.push_str(&verbalize_number_before(&digit.to_string(), Some(unit))?);
+            result.push(unit);
+        }
+    }
+    Some(result)
+}
+
+/// Verbalizes one match of normalization_re. next is the character after the match. Returns
+/// None if the match should be left as it is.
+fn verbalize_match(caps: &Captures, next: Option<char>) -> Option<String> {
+    let group = |name: &str| caps.name(name).map(|m| ascii_digits(m.as_str()));
+    if group("ymd").is_some() {
+        verbalize_date(&group("ymd_y")?, &group("ymd_m")?, &group("ymd_d")?)
+    } else if group("dmy").is_some() {
+        verbalize_date(&group("dmy_y")?, &group("dmy_m")?, &group("dmy_d")?)
+    } else if group("time").is_some() {
+        verbalize_time(&group("time_h")?, &group("time_m")?, group("time_ampm").as_deref())
+    } else if group("hour").is_some() {
+        verbalize_time(&group("hour_h")?, "0", group("hour_ampm").as_deref())
+    } else if group("year").is_some() {
+        Some(format!("{}年", verbalize_digits(&group("year_y")?)?))
+    } else if group("money").is_some() {
+        verbalize_money(&group("currency")?, &group("amount")?, group("multiplier").as_deref())
+    } else if group("percent").is_some() {
+        Some(format!("百分之{}", verbalize_number(&group("percent_n")?)?))
+    } else if group("unit").is_some() {
+        let unit = match group("unit_name")?.as_ref() {
+            "km" => "公里",
+            "kg" => "公斤",
+            "cm" => "厘米",
+            "mm" => "毫米",
+            "ml" => "毫升",
+            "m" => "米",
+            "g" => "克",
+            _ => "度",
+        };
+        Some(format!("{}{}", verbalize_number_before(&group("unit_n")?, unit.chars().next())?, unit))
+    } else if let Some(number) = group("number") {
+        verbalize_number_before(&number, next)
+    } else {
+        let abbreviation = group("abbreviation")?;
+        ABBREVIATIONS.iter().find(|(a, _)| *a == abbreviation).map(|(_, v)| v.to_string())
+    }
+}
+
+/// Splits the string into pieces that add up to the string, as (original text, normalized text).
+/// Pieces that are not changed by the normalization have the same original and normalized text.
+/// Expressions that are written together with other Latin letters or digits are left as they are,
+/// so e.g. "3D" and "MP3" are not changed, and so are expressions inside words of wordlist, so e.g.
+/// "7-11" and "133" are read as the words.
+pub fn normalize_text_pieces(s: &str) -> Vec<(String, String)> {
+    normalize_text_pieces_with_model(s, segmentation::default_segmenter())
+}
+
+/// Same as normalize_text_pieces, but expressions inside words of the model are left as they are.
+pub fn normalize_text_pieces_with_model(s: &str, model: &dyn segmentation::SegmentationModel) -> Vec<(String, String)> {
+    let words : Vec<segmentation::Token> = segmentation::segment_text_with_model(s, model).tokens.into_iter()
+        .filter(|token| token.in_dictionary())
+        .collect();
+    let mut pieces : Vec<(String, String)> = vec![];
+    let push_unchanged = |pieces: &mut Vec<(String, String)>, text: &str| {
+        if text.is_empty() {
+            return;
+        }
+        match pieces.last_mut() {
+            Some((original, normalized)) if original == normalized => {
+                original.push_str(text);
+                normalized.push_str(text);
+            },
+            _ => pieces.push((text.to_string(), text.to_string())),
+        }
+    };
+
+    let mut last = 0;
+    for caps in normalization_re().captures_iter(s) {
+        let m = caps.get(0).unwrap();
+        let prev = s[..m.start()].chars().next_back();
+        let next = s[m.end()..].chars().next();
+        let mut text = m.as_str();
+        let mut start = m.start();
+
+        // A minus sign after a word or number is a dash, e.g. "7-11"
+        if caps.name("number").is_some() && text.starts_with('-') && prev.is_some_and(|c| c.is_ascii_alphanumeric()) {
+            text = &text[1..];
+            start += 1;
+        }
+        let prev = s[..start].chars().next_back();
+        let attached = |a: Option<char>, b: Option<char>| [a, b].iter().all(|c| c.is_some_and(|c| c.is_ascii_alphanumeric()));
+        let in_word = words.iter().any(|word| word.byte_start <= m.start() && m.end() <= word.byte_end);
+        let normalized = if in_word || attached(prev, text.chars().next()) || attached(text.chars().next_back(), next) {
+            None
+        } else if start != m.start() {
+            verbalize_number_before(&ascii_digits(text), next)
+        } else {
+            verbalize_match(&caps, next)
+        };
+
+        push_unchanged(&mut pieces, &s[last..start]);
+        match normalized {
+            Some(normalized) => pieces.push((text.to_string(), normalized)),
+            None => push_unchanged(&mut pieces, text),
+        }
+        last = m.end();
+    }
+    push_unchanged(&mut pieces, &s[last..]);
+    pieces
+}
+
+/// Normalizes the string for pronunciation lookup, by verbalizing numbers, dates, times,
+/// percentages, amounts of money and common abbreviations into Cantonese words, e.g. "HK$1,500"
+/// => "一千五百蚊" and "10:30pm" => "下晝十點半". See normalize_text_pieces for what is left as
+/// it is.
+pub fn normalize_text(s: &str) -> String {
+    normalize_text_pieces(s).into_iter().map(|(_, normalized)| normalized).collect()
+}
@@ -0,0 +1,129 @@
+/*!
+Reverse lookup from pronunciations to characters and words, e.g. for homophones or IME-style
+search. The indices are built lazily from charlist and wordlist the first time they are used.
+*/
+
+use crate::cantonese;
+use crate::data;
+use crate::jyutping::Syllable;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// How the pronunciations in a query are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryMode {
+    /// Syllables with tones, e.g. "sik6 faan6".
+    Exact,
+    /// Syllables without tones, e.g. "sik faan". Tones in the query are ignored.
+    Toneless,
+    /// Initials only, e.g. "s f". Use "0" for the null initial.
+    Initials,
+}
+
+/// The key of a syllable in the index for the mode.
+fn syllable_key(syllable: &Syllable, mode: QueryMode) -> String {
+    match mode {
+        QueryMode::Exact => syllable.to_string(),
+        QueryMode::Toneless => syllable.toneless(),
+        QueryMode::Initials if syllable.initial().is_empty() => "0".to_string(),
+        QueryMode::Initials => syllable.initial().to_string(),
+    }
+}
+
+/// The key of a query for the mode. Query syllables are normalized the same way as the indexed
+/// ones, so e.g. "Sik6" and "sik6" are the same query.
+fn query_key(query: &str, mode: QueryMode) -> String {
+    query.split_whitespace().map(|token| {
+        let token = token.to_lowercase();
+        match mode {
+            QueryMode::Exact => token,
+            QueryMode::Toneless => token.trim_end_matches(|c: char| c.is_ascii_digit()).to_string(),
+            QueryMode::Initials => token,
+        }
+    }).collect::<Vec<String>>().join(" ")
+}
+
+type CharIndex = HashMap<String, Vec<(char, &'static str, u64)>>;
+type WordIndex = HashMap<String, Vec<(&'static str, &'static str, u64)>>;
+
+fn build_char_index(mode: QueryMode) -> CharIndex {
+    let mut index : CharIndex = HashMap::new();
+    for (ch, readings) in data::cantonese_charlist_with_jyutping() {
+        for (p, count) in readings {
+            if let Ok(syllable) = p.parse::<Syllable>() {
+                index.entry(syllable_key(&syllable, mode)).or_default().push((*ch, p.as_str(), *count));
+            }
+        }
+    }
+    for entries in index.values_mut() {
+        entries.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)).then_with(|| a.1.cmp(b.1)));
+    }
+    index
+}
+
+/// The count of a word with the pronunciation, which is the charlist count of its least common
+/// character reading (0 if a character doesn't have the reading in charlist).
+pub(crate) fn word_count(word: &str, syllables: &[Syllable]) -> u64 {
+    let charlist = data::cantonese_charlist_with_jyutping();
+    if word.chars().count() != syllables.len() {
+        return 0;
+    }
+    word.chars().zip(syllables)
+        .map(|(ch, s)| charlist.get(&ch).and_then(|ps| ps.get(&s.to_string())).copied().unwrap_or(0))
+        .min()
+        .unwrap_or(0)
+}
+
+fn build_word_index(mode: QueryMode) -> WordIndex {
+    let mut index : WordIndex = HashMap::new();
+    for (word, p, syllables) in cantonese::parsed_wordlist() {
+        let key = syllables.iter().map(|s| syllable_key(s, mode)).collect::<Vec<String>>().join(" ");
+        index.entry(key).or_default().push((word, p, word_count(word, syllables)));
+    }
+    for entries in index.values_mut() {
+        entries.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)).then_with(|| a.1.cmp(b.1)));
+    }
+    index
+}
+
+fn char_index(mode: QueryMode) -> &'static CharIndex {
+    static EXACT: OnceLock<CharIndex> = OnceLock::new();
+    static TONELESS: OnceLock<CharIndex> = OnceLock::new();
+    static INITIALS: OnceLock<CharIndex> = OnceLock::new();
+    let cell = match mode {
+        QueryMode::Exact => &EXACT,
+        QueryMode::Toneless => &TONELESS,
+        QueryMode::Initials => &INITIALS,
+    };
+    cell.get_or_init(|| build_char_index(mode))
+}
+
+fn word_index(mode: QueryMode) -> &'static WordIndex {
+    static EXACT: OnceLock<WordIndex> = OnceLock::new();
+    static TONELESS: OnceLock<WordIndex> = OnceLock::new();
+    static INITIALS: OnceLock<WordIndex> = OnceLock::new();
+    let cell = match mode {
+        QueryMode::Exact => &EXACT,
+        QueryMode::Toneless => &TONELESS,
+        QueryMode::Initials => &INITIALS,
+    };
+    cell.get_or_init(|| build_word_index(mode))
+}
+
+/// Gets the characters that are read as the syllable, as (character, reading, charlist count),
+/// from the most common to the least common. The same character can be returned more than once if
+/// more than one of its readings matches the query (e.g. in Toneless mode).
+pub fn chars_with_pronunciation(query: &str, mode: QueryMode) -> Vec<(char, String, u64)> {
+    char_index(mode).get(&query_key(query, mode))
+        .map(|entries| entries.iter().map(|(ch, p, count)| (*ch, p.to_string(), *count)).collect())
+        .unwrap_or_default()
+}
+
+/// Gets the words in wordlist that are read as the syllables, as (word, pronunciation, count),
+/// from the most common to the least common. The count of a word is the charlist count of its
+/// least common character reading, so it is 0 for words with characters that are not in charlist.
+pub fn words_with_pronunciation(query: &str, mode: QueryMode) -> Vec<(String, String, u64)> {
+    word_index(mode).get(&query_key(query, mode))
+        .map(|entries| entries.iter().map(|(w, p, count)| (w.to_string(), p.to_string(), *count)).collect())
+        .unwrap_or_default()
+}
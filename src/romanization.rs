@@ -0,0 +1,430 @@
+/*!
+Conversion between Jyutping and other Cantonese romanization schemes, and from Jyutping to IPA.
+The conversions work on jyutping::Syllable, so they only accept what the Jyutping parser accepts.
+*/
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::jyutping::{self, Syllable};
+
+// Yale spelling of Jyutping initials. The initials not listed here are spelt the same way.
+const YALE_INITIALS : [(&str, &str); 3] = [("z", "j"), ("c", "ch"), ("j", "y")];
+
+// Yale spelling of Jyutping finals. The finals not listed here are spelt the same way, except for
+// UNSPELLABLE_FINALS.
+const YALE_FINALS : [(&str, &str); 7] = [
+    ("aa", "a"),
+    ("oe", "eu"),
+    ("oeng", "eung"),
+    ("oek", "euk"),
+    ("eoi", "eui"),
+    ("eon", "eun"),
+    ("eot", "eut"),
+];
+
+// Jyutping finals (both only used in colloquial syllables) that have no Yale spelling of their own:
+// eu would be spelt like oe, and oet like eot, so they couldn't be converted back.
+const UNSPELLABLE_FINALS : [&str; 2] = ["eu", "oet"];
+
+const COMBINING_GRAVE : char = '\u{300}';
+const COMBINING_ACUTE : char = '\u{301}';
+const COMBINING_MACRON : char = '\u{304}';
+
+// Precomposed characters used in Yale, with their base letters and combining marks
+const PRECOMPOSED : [(char, char, char); 18] = [
+    ('ā', 'a', COMBINING_MACRON), ('á', 'a', COMBINING_ACUTE), ('à', 'a', COMBINING_GRAVE),
+    ('ē', 'e', COMBINING_MACRON), ('é', 'e', COMBINING_ACUTE), ('è', 'e', COMBINING_GRAVE),
+    ('ī', 'i', COMBINING_MACRON), ('í', 'i', COMBINING_ACUTE), ('ì', 'i', COMBINING_GRAVE),
+    ('ō', 'o', COMBINING_MACRON), ('ó', 'o', COMBINING_ACUTE), ('ò', 'o', COMBINING_GRAVE),
+    ('ū', 'u', COMBINING_MACRON), ('ú', 'u', COMBINING_ACUTE), ('ù', 'u', COMBINING_GRAVE),
+    ('ḿ', 'm', COMBINING_ACUTE),
+    ('ń', 'n', COMBINING_ACUTE), ('ǹ', 'n', COMBINING_GRAVE),
+];
+
+fn is_yale_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+/// Adds a combining mark to a letter, using the precomposed character if there is one.
+fn add_mark(c: char, mark: char) -> String {
+    PRECOMPOSED.iter().find(|(_, base, m)| *base == c && *m == mark)
+        .map(|(p, _, _)| p.to_string())
+        .unwrap_or_else(|| format!("{}{}", c, mark))
+}
+
+/// Converts a Jyutping syllable to Yale. With diacritics, tones are marked with accents (a macron
+/// for tone 1, which is also used for the high falling tone) and an "h" after the vowels for the
+/// low tones (4, 5, 6), e.g. "nàahm" for naam4. Without diacritics, the Jyutping tone number is
+/// appended instead, e.g. "naam4". Returns None for the finals eu and oet, which Yale can't tell
+/// apart from oe and eot.
+pub fn syllable_to_yale(syllable: &Syllable, diacritics: bool) -> Option<String> {
+    if UNSPELLABLE_FINALS.contains(&syllable.final_()) {
+        return None;
+    }
+    let initial = YALE_INITIALS.iter().find(|(j, _)| *j == syllable.initial()).map_or(syllable.initial(), |(_, y)| y);
+    let final_ = YALE_FINALS.iter().find(|(j, _)| *j == syllable.final_()).map_or(syllable.final_(), |(_, y)| y);
+    // Yale doesn't repeat the y in Jyutping jyu (e.g. jyu4 is yùh)
+    let final_ = if initial == "y" && final_.starts_with('y') { &final_[1..] } else { final_ };
+
+    if !diacritics {
+        return Some(format!("{}{}{}", initial, final_, syllable.tone()));
+    }
+
+    let mark = match syllable.tone() {
+        1 => Some(COMBINING_MACRON),
+        2 | 5 => Some(COMBINING_ACUTE),
+        4 => Some(COMBINING_GRAVE),
+        _ => None,
+    };
+    let low = syllable.tone() >= 4;
+
+    // The mark goes on the first vowel, or on the nasal of syllabic m/ng. The low tone "h" goes
+    // after the vowels (or the syllabic nasal), before any consonant coda.
+    let chars : Vec<char> = final_.chars().collect();
+    let (mark_idx, h_idx) = match chars.iter().position(|c| is_yale_vowel(*c)) {
+        Some(idx) => (idx, chars.iter().rposition(|c| is_yale_vowel(*c)).unwrap() + 1), // unwrap: position found a vowel
+        None => (0, chars.len()),
+    };
+    let mut result = initial.to_string();
+    for (i, c) in chars.iter().enumerate() {
+        if i == h_idx && low {
+            result.push('h');
+        }
+        match mark {
+            Some(mark) if i == mark_idx => result.push_str(&add_mark(*c, mark)),
+            _ => result.push(*c),
+        }
+    }
+    if h_idx == chars.len() && low {
+        result.push('h');
+    }
+    Some(result)
+}
+
+/// Converts a Yale syllable, with either diacritics or tone numbers, to Jyutping. Returns None if
+/// the syllable is not valid Yale.
+pub fn yale_to_syllable(yale: &str) -> Option<Syllable> {
+    // Separate the tone marks (or number) from the letters
+    let mut letters = String::new();
+    let mut mark = None;
+    let mut number = None;
+    for c in yale.to_lowercase().chars() {
+        if let Some((_, base, m)) = PRECOMPOSED.iter().find(|(p, _, _)| *p == c) {
+            letters.push(*base);
+            mark = Some(*m);
+        } else if matches!(c, COMBINING_GRAVE | COMBINING_ACUTE | COMBINING_MACRON) {
+            mark = Some(c);
+        } else if let Some(d) = c.to_digit(10) {
+            number = Some(d as u8);
+        } else {
+            letters.push(c);
+        }
+    }
+
+    let split = letters.find(is_yale_vowel).or_else(|| letters.rfind("ng").or_else(|| letters.rfind('m')))?;
+    let (mut initial, mut rest) = letters.split_at(split);
+    // The y of the final yu is not a vowel (e.g. chyu), but Yale y on its own is an initial
+    if initial.len() > 1 && initial.ends_with('y') {
+        (initial, rest) = letters.split_at(split - 1);
+    }
+
+    // Look for the low tone "h" after the vowels (or the syllabic nasal)
+    let vowels_end = match rest.rfind(is_yale_vowel) {
+        Some(idx) => idx + 1,
+        None => if rest.starts_with("ng") { 2 } else { 1 },
+    };
+    let low = number.is_none() && rest[vowels_end..].starts_with('h');
+    let final_ = if low { format!("{}{}", &rest[..vowels_end], &rest[vowels_end + 1..]) } else { rest.to_string() };
+
+    let tone = match (number, mark, low) {
+        (Some(n), None, _) => n,
+        (Some(_), Some(_), _) => return None,
+        (None, Some(COMBINING_MACRON), false) | (None, Some(COMBINING_GRAVE), false) => 1,
+        (None, Some(COMBINING_ACUTE), false) => 2,
+        (None, None, false) => 3,
+        (None, Some(COMBINING_GRAVE), true) => 4,
+        (None, Some(COMBINING_ACUTE), true) => 5,
+        (None, None, true) => 6,
+        _ => return None,
+    };
+
+    let jyutping_initial = YALE_INITIALS.iter().find(|(_, y)| *y == initial).map_or(initial, |(j, _)| j);
+    // Yale "a" on its own is Jyutping "aa"
+    let jyutping_final = if final_ == "a" { "aa" } else { YALE_FINALS.iter().find(|(_, y)| *y == final_).map_or(final_.as_str(), |(j, _)| j) };
+
+    // Yale y is Jyutping j, but Yale merges the y of Jyutping jyu, so we need to put the y back
+    // if j+final isn't a syllable (e.g. yuhn is jyun4 but yuhng is jung4)
+    if jyutping_initial == "j" && jyutping_final.starts_with('u') {
+        let syllable = Syllable::new("j", jyutping_final, tone)?;
+        if syllable.is_attested() {
+            return Some(syllable);
+        }
+        return Syllable::new("j", &format!("y{}", jyutping_final), tone);
+    }
+    Syllable::new(jyutping_initial, jyutping_final, tone)
+}
+
+/// Converts a Jyutping string to Yale, with diacritics or tone numbers. See syllable_to_yale.
+/// Returns None if the string is not valid Jyutping, or has a syllable with no Yale spelling.
+pub fn jyutping_to_yale(jyutping: &str, diacritics: bool) -> Option<String> {
+    Some(jyutping::parse_jyutping(jyutping)?.iter().map(|s| syllable_to_yale(s, diacritics)).collect::<Option<Vec<String>>>()?.join(" "))
+}
+
+/// Converts a Yale string (syllables separated by spaces) to Jyutping. Both diacritics and tone
+/// numbers are accepted. Returns None if the string is not valid Yale.
+pub fn yale_to_jyutping(yale: &str) -> Option<String> {
+    Some(yale.split_whitespace().map(|s| yale_to_syllable(s).map(|s| s.to_string())).collect::<Option<Vec<String>>>()?.join(" "))
+}
+
+/// How tones are written in IPA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneNotation {
+    /// Chao tone letters, e.g. "˧˥" for tone 2.
+    ChaoLetters,
+    /// Superscript Chao tone numbers, e.g. "³⁵" for tone 2.
+    SuperscriptNumbers,
+}
+
+// IPA for Jyutping initials
+const IPA_INITIALS : [(&str, &str); 19] = [
+    ("b", "p"), ("p", "pʰ"), ("m", "m"), ("f", "f"),
+    ("d", "t"), ("t", "tʰ"), ("n", "n"), ("l", "l"),
+    ("g", "k"), ("k", "kʰ"), ("ng", "ŋ"), ("h", "h"),
+    ("gw", "kʷ"), ("kw", "kʷʰ"), ("w", "w"),
+    ("z", "t͡s"), ("c", "t͡sʰ"), ("s", "s"), ("j", "j"),
+];
+
+// IPA for Jyutping nuclei, when not followed by a coda that changes them (see syllable_to_ipa)
+const IPA_NUCLEI : [(&str, &str); 11] = [
+    ("aa", "aː"), ("a", "ɐ"), ("e", "ɛː"), ("i", "iː"), ("o", "ɔː"), ("u", "uː"),
+    ("oe", "œː"), ("eo", "ɵ"), ("yu", "yː"),
+    ("m", "m̩"), ("ng", "ŋ̍"),
+];
+
+// IPA for Jyutping codas
+const IPA_CODAS : [(&str, &str); 8] = [
+    ("i", "i̯"), ("u", "u̯"), ("m", "m"), ("n", "n"), ("ng", "ŋ"), ("p", "p̚"), ("t", "t̚"), ("k", "k̚"),
+];
+
+/// The tone contour of a syllable in Chao tone numbers. The entering tones are short, so they only
+/// get one number.
+fn tone_contour(syllable: &Syllable) -> &'static str {
+    match (syllable.tone(), syllable.is_checked()) {
+        (1, false) => "55",
+        (2, _) => "35",
+        (3, false) => "33",
+        (4, _) => "21",
+        (5, _) => "13",
+        (6, false) => "22",
+        (1, true) => "5",
+        (3, true) => "3",
+        (6, true) => "2",
+        _ => unreachable!("Syllable tones are 1-6"),
+    }
+}
+
+/// Converts a Jyutping syllable to IPA (a broad transcription), e.g. "kʷɔːŋ³⁵" for gwong2 with
+/// superscript numbers, or "kʷɔːŋ˧˥" with Chao tone letters. Level tones are written with a single
+/// tone letter (e.g. "˥" for tone 1).
+pub fn syllable_to_ipa(syllable: &Syllable, tones: ToneNotation) -> String {
+    let initial = IPA_INITIALS.iter().find(|(j, _)| *j == syllable.initial()).map_or("", |(_, i)| i);
+    let (nucleus, coda) = (syllable.nucleus(), syllable.coda());
+    // Some nuclei are pronounced differently depending on the coda
+    let nucleus_ipa = match (nucleus, coda) {
+        ("i", "ng" | "k") => "ɪ",
+        ("u", "ng" | "k") => "ʊ",
+        ("e", "i") => "e",
+        ("o", "u") => "o",
+        _ => IPA_NUCLEI.iter().find(|(j, _)| *j == nucleus).map_or(nucleus, |(_, i)| i),
+    };
+    // The i coda is rounded after rounded vowels (oi, ui, eoi)
+    let coda_ipa = match (nucleus, coda) {
+        ("o" | "u" | "eo", "i") => "y̯",
+        _ => IPA_CODAS.iter().find(|(j, _)| *j == coda).map_or(coda, |(_, i)| i),
+    };
+
+    let contour = tone_contour(syllable);
+    let tone : String = match tones {
+        ToneNotation::SuperscriptNumbers => contour.chars().map(|c| match c {
+            '1' => '¹', '2' => '²', '3' => '³', '5' => '⁵', _ => c,
+        }).collect(),
+        ToneNotation::ChaoLetters => {
+            let letters : String = contour.chars().map(|c| match c {
+                '1' => '˩', '2' => '˨', '3' => '˧', '4' => '˦', '5' => '˥', _ => c,
+            }).collect();
+            // A level tone is written with one letter
+            match letters.chars().collect::<Vec<char>>()[..] {
+                [a, b] if a == b => a.to_string(),
+                _ => letters,
+            }
+        }
+    };
+
+    format!("{}{}{}{}", initial, nucleus_ipa, coda_ipa, tone)
+}
+
+/// Splits the punctuation at the start and the end of a token off, as (leading punctuation, word,
+/// trailing punctuation). A leading "!" is kept with the word, since it marks exceptions such as
+/// "!bas4".
+fn split_punctuation(token: &str) -> (&str, &str, &str) {
+    let word = token.trim_start_matches(|c: char| !c.is_alphanumeric() && c != '!');
+    let start = token.len() - word.len();
+    let word = word.trim_end_matches(|c: char| !c.is_alphanumeric());
+    (&token[..start], word, &token[start + word.len()..])
+}
+
+/// Converts the Jyutping in a string (e.g. the output of cantonese::get_ping3jam1) to IPA. The
+/// string is split on whitespace, and each token that is valid Jyutping is converted, with a "."
+/// between the syllables if the token has more than one (e.g. "nei5hou2"). Punctuation attached
+/// to a token (e.g. "waa2,") is kept around the converted token. Other tokens (e.g. punctuation or
+/// exceptions like "!bas4") and the whitespace are kept unchanged.
+pub fn jyutping_to_ipa(s: &str, tones: ToneNotation) -> String {
+    s.split_inclusive(char::is_whitespace).map(|piece| {
+        let token = piece.trim_end_matches(char::is_whitespace);
+        let whitespace = &piece[token.len()..];
+        let (before, word, after) = split_punctuation(token);
+        match jyutping::parse_jyutping(word) {
+            Some(syllables) if !syllables.is_empty() => {
+                let ipa : Vec<String> = syllables.iter().map(|s| syllable_to_ipa(s, tones)).collect();
+                format!("{}{}{}{}", before, ipa.join("."), after, whitespace)
+            },
+            _ => piece.to_string(),
+        }
+    }).collect()
+}
+
+/// Looks up a spelling in a table of (Jyutping spelling, other spelling). Spellings not in the
+/// table are the same in both schemes.
+fn respell(table: &[(&'static str, &'static str)], jyutping: &'static str) -> &'static str {
+    table.iter().find(|(j, _)| *j == jyutping).map_or(jyutping, |(_, s)| s)
+}
+
+/// Builds a map of (toneless spelling in another scheme) => (Jyutping initial and final) for the
+/// attested syllables (see jyutping::syllable_inventory). Spellings shared by more than one
+/// syllable are ambiguous and are left out.
+fn reverse_spellings(spell: fn(&'static str, &'static str) -> String) -> HashMap<String, (&'static str, &'static str)> {
+    let mut map : HashMap<String, Vec<(&'static str, &'static str)>> = HashMap::new();
+    for (final_, initials) in jyutping::syllable_inventory() {
+        for initial in initials {
+            map.entry(spell(initial, final_)).or_default().push((initial, final_));
+        }
+    }
+    map.into_iter().filter(|(_, v)| v.len() == 1).map(|(k, v)| (k, v[0])).collect()
+}
+
+/// Splits a syllable into its letters and its tone number. Returns None if there is no tone
+/// number.
+fn split_tone(syllable: &str) -> Option<(String, u8)> {
+    let letters = syllable.trim_end_matches(|c: char| c.is_ascii_digit());
+    let tone = syllable[letters.len()..].parse().ok()?;
+    Some((letters.to_lowercase(), tone))
+}
+
+// Cantonese Pinyin (教院式) spelling of Jyutping initials and finals. Those not listed here are
+// spelt the same way. Jyutping oet and eot are both spelt "oet".
+const CANTONESE_PINYIN_INITIALS : [(&str, &str); 2] = [("z", "dz"), ("c", "ts")];
+const CANTONESE_PINYIN_FINALS : [(&str, &str); 6] = [
+    ("yu", "y"), ("yun", "yn"), ("yut", "yt"),
+    ("eoi", "oey"), ("eon", "oen"), ("eot", "oet"),
+];
+
+fn cantonese_pinyin_spelling(initial: &'static str, final_: &'static str) -> String {
+    format!("{}{}", respell(&CANTONESE_PINYIN_INITIALS, initial), respell(&CANTONESE_PINYIN_FINALS, final_))
+}
+
+fn cantonese_pinyin_reverse() -> &'static HashMap<String, (&'static str, &'static str)> {
+    static DATA: OnceLock<HashMap<String, (&'static str, &'static str)>> = OnceLock::new();
+    DATA.get_or_init(|| reverse_spellings(cantonese_pinyin_spelling))
+}
+
+/// Converts a Jyutping syllable to Cantonese Pinyin (教院式), the scheme used by the EDB in Hong Kong
+/// schools, e.g. "dzoey2" for zeoi2. Entering tone syllables use tones 7, 8 and 9 instead of 1, 3
+/// and 6 (e.g. "sik9" for sik6).
+pub fn syllable_to_cantonese_pinyin(syllable: &Syllable) -> String {
+    let tone = match (syllable.tone(), syllable.is_checked()) {
+        (1, true) => 7,
+        (3, true) => 8,
+        (6, true) => 9,
+        (tone, _) => tone,
+    };
+    format!("{}{}", cantonese_pinyin_spelling(syllable.initial(), syllable.final_()), tone)
+}
+
+/// Converts a Cantonese Pinyin syllable (with a tone number) to Jyutping. Tones 1, 3 and 6 are
+/// accepted for entering tone syllables as well as 7, 8 and 9. Returns None if the syllable is not
+/// valid Cantonese Pinyin, or if it is ambiguous (e.g. "tsoet" is both ceot and coet).
+pub fn cantonese_pinyin_to_syllable(s: &str) -> Option<Syllable> {
+    let (letters, tone) = split_tone(s)?;
+    let (initial, final_) = cantonese_pinyin_reverse().get(&letters)?;
+    let jyutping_tone = match tone {
+        7 => 1,
+        8 => 3,
+        9 => 6,
+        tone => tone,
+    };
+    let syllable = Syllable::new(initial, final_, jyutping_tone)?;
+    if tone >= 7 && !syllable.is_checked() {
+        return None;
+    }
+    Some(syllable)
+}
+
+/// Converts a Jyutping string to Cantonese Pinyin. See syllable_to_cantonese_pinyin. Returns None
+/// if the string is not valid Jyutping.
+pub fn jyutping_to_cantonese_pinyin(jyutping: &str) -> Option<String> {
+    Some(jyutping::parse_jyutping(jyutping)?.iter().map(syllable_to_cantonese_pinyin).collect::<Vec<String>>().join(" "))
+}
+
+/// Converts a Cantonese Pinyin string (syllables separated by spaces) to Jyutping. Returns None if
+/// any syllable can't be converted. See cantonese_pinyin_to_syllable.
+pub fn cantonese_pinyin_to_jyutping(s: &str) -> Option<String> {
+    Some(s.split_whitespace().map(|s| cantonese_pinyin_to_syllable(s).map(|s| s.to_string())).collect::<Option<Vec<String>>>()?.join(" "))
+}
+
+// Sidney Lau spelling of Jyutping initials and finals. Those not listed here are spelt the same
+// way.
+const SIDNEY_LAU_INITIALS : [(&str, &str); 3] = [("z", "j"), ("c", "ch"), ("j", "y")];
+const SIDNEY_LAU_FINALS : [(&str, &str); 17] = [
+    ("aa", "a"),
+    ("o", "oh"), ("ou", "o"),
+    ("u", "oo"), ("ui", "ooi"), ("un", "oon"), ("ut", "oot"),
+    ("oe", "euh"), ("oeng", "eung"), ("oek", "euk"), ("oet", "eut"),
+    ("eoi", "ui"), ("eon", "un"), ("eot", "ut"),
+    ("yu", "ue"), ("yun", "uen"), ("yut", "uet"),
+];
+
+fn sidney_lau_spelling(initial: &'static str, final_: &'static str) -> String {
+    format!("{}{}", respell(&SIDNEY_LAU_INITIALS, initial), respell(&SIDNEY_LAU_FINALS, final_))
+}
+
+fn sidney_lau_reverse() -> &'static HashMap<String, (&'static str, &'static str)> {
+    static DATA: OnceLock<HashMap<String, (&'static str, &'static str)>> = OnceLock::new();
+    DATA.get_or_init(|| reverse_spellings(sidney_lau_spelling))
+}
+
+/// Converts a Jyutping syllable to Sidney Lau romanization, e.g. "yuet6" for jyut6. The tone is
+/// written as a number after the syllable. Jyutping doesn't distinguish Sidney Lau's high falling
+/// (1) and high level (1°) tones, so both are written as 1.
+pub fn syllable_to_sidney_lau(syllable: &Syllable) -> String {
+    format!("{}{}", sidney_lau_spelling(syllable.initial(), syllable.final_()), syllable.tone())
+}
+
+/// Converts a Sidney Lau syllable (with a tone number, optionally followed by "°") to Jyutping.
+/// Returns None if the syllable is not valid Sidney Lau.
+pub fn sidney_lau_to_syllable(s: &str) -> Option<Syllable> {
+    let (letters, tone) = split_tone(s.trim_end_matches('°'))?;
+    let (initial, final_) = sidney_lau_reverse().get(&letters)?;
+    Syllable::new(initial, final_, tone)
+}
+
+/// Converts a Jyutping string to Sidney Lau romanization. See syllable_to_sidney_lau. Returns None
+/// if the string is not valid Jyutping.
+pub fn jyutping_to_sidney_lau(jyutping: &str) -> Option<String> {
+    Some(jyutping::parse_jyutping(jyutping)?.iter().map(syllable_to_sidney_lau).collect::<Vec<String>>().join(" "))
+}
+
+/// Converts a Sidney Lau string (syllables separated by spaces) to Jyutping. Returns None if any
+/// syllable can't be converted.
+pub fn sidney_lau_to_jyutping(s: &str) -> Option<String> {
+    Some(s.split_whitespace().map(|s| sidney_lau_to_syllable(s).map(|s| s.to_string())).collect::<Option<Vec<String>>>()?.join(" "))
+}
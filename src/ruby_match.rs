@@ -4,8 +4,10 @@ to generate ruby text. Uses a variant of Longest Common Subsequence to generate
 the best match.
 */
 
+use crate::cantonese;
 use crate::common;
 use crate::data;
+use crate::jyutping::Syllable;
 use std::collections::{HashMap, HashSet};
 use std::sync::OnceLock;
 
@@ -16,17 +18,26 @@ fn cantonese_charlist_half() -> &'static HashMap<char, Vec<String>> {
     DATA.get_or_init(|| {
         let charlist = data::cantonese_charlist_with_jyutping();
         charlist.iter()
-            .map(|(ch, pd)| (*ch, pd.keys().map(|p| p.trim_end_matches(&['1', '2', '3', '4', '5', '6']).to_string()).collect()))
+            .map(|(ch, pd)| (*ch, pd.keys().map(|p| toneless(p)).collect()))
             .collect()
     })
 }
 
+// Strips the tone from a pronunciation. Pronunciations that are not valid Jyutping syllables (e.g.
+// ones that are already missing the tone) are returned with any trailing digits removed.
+fn toneless(p: &str) -> String {
+    p.parse::<Syllable>().map(|s| s.toneless()).unwrap_or_else(|_| p.trim_end_matches(|c: char| c.is_ascii_digit()).to_string())
+}
+
 /// Maximum items for either text or pronunciation input. This is required because the algorithm is
 /// O(n^2) and can be slow/memory intensive for long inputs.
 pub const RUBY_MATCH_MAX : u32 = 300;
 
 const FULL_MATCH_SCORE : i32 = 1000;
 const HALF_MATCH_SCORE : i32 = 300;
+// Score for a pronunciation that is a known changed tone (變調) of the character (see
+// cantonese::changed_tone_base), which is more likely to be right than other tone mismatches.
+const CHANGED_TONE_MATCH_SCORE : i32 = 600;
 
 // Some token value to force the lcs to prefer one unmatched situation over
 // another. Not totally sure what value this should contain to be correct.
@@ -276,10 +287,18 @@ impl RubyMatch {
                 }
                 // }
 
-                // Case 4: we try to match the half part of the pronunciation
-                if cantonese_charlist_half().get(&te0).map_or(false, |ps| ps.contains(&pe.trim_end_matches(&['1', '2', '3', '4', '5', '6']).to_string())) {
+                // Case 4: we try to match the pronunciation as a changed tone, or failing that, the
+                // half part of the pronunciation
+                let match_score = if cantonese::changed_tone_base(te0, &pe).is_some() {
+                    Some(CHANGED_TONE_MATCH_SCORE)
+                } else if cantonese_charlist_half().get(&te0).map_or(false, |ps| ps.contains(&toneless(&pe))) {
+                    Some(HALF_MATCH_SCORE)
+                } else {
+                    None
+                };
+                if let Some(match_score) = match_score {
                     let targ = (t_i - 1, p_j - 1);
-                    let tv = self._lcs(targ) + HALF_MATCH_SCORE;
+                    let tv = self._lcs(targ) + match_score;
                     if tv > v {
                         // println!("half match: te0{} pe{} t_i{} p_j{} tv{}", te0, pe, t_i, p_j, tv);
                         the_arg = targ;
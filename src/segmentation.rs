@@ -1,158 +1,753 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
-use crate::data;
-
-/// Constants
-const BT_MATCH: i32 = 0;
-const BT_ODD: i32 = -1;
-const BIG_VALUE : f32 = f32::INFINITY;
-
-/// Memoized recursive function (i.e. "DP" as we call it)
-fn _dp(
-    a: usize,
-    b: usize,
-    s: &Vec<char>,
-    d: &HashSet<String>,
-    dp: &mut HashMap<(usize, usize), f32>,
-    bt: &mut HashMap<(usize, usize), i32>,
-) -> f32 {
-    // TODO: we can tweak the recurrence function a bit later. But this is the
-    // generic one that's useful enough.
-
-    /*" Recurrence function:
-    f(a,b) = {
-        // yay, good stuff here  -- 1/(b-a) is explained in note #1, position factor is to
-        // encourage earlier matches
-        1/(b-a) + 2n-a-b/POSITION_FACTOR if s[a:b] in input_set,
-
-        // big cost -- note #2
-        10 if b - a == 1
-
-        min( f(a, i) + f(i, b) for i in [a..b] )
-    }
-
-    Note #1: Supposedly the cost should be near zero, but we prefer longer
-             words than short ones. The best heuristical value probably still
-             needs to be tweaked.
-    Note #2: Just needs to be significantly larger than the value in #1.
-    "*/
-
-    assert!(a < b);
-
-    if let Some(&res) = dp.get(&(a, b)) {
-        return res;
-    }
-
-    let mut bt_res = 0;
-    let mut res = BIG_VALUE;
-
-    // if s[a:b] is in the dictionary
-    if d.contains(&s[a..b].iter().collect::<String>()) {
-        res = 1.0 / (b - a) as f32;
-        bt_res = BT_MATCH;
-    } else if b - a == 1 {
-        res = 10.0;
-        bt_res = BT_ODD;
-    } else {
-        for i in a + 1..b {
-            let v = _dp(a, i, s, d, dp, bt) + _dp(i, b, s, d, dp, bt);
-            if v <= res { // <= is used here to encourage partitioning at later rather than earlier, to encourage "sticking" of words earlier
-                res = v;
-                bt_res = i.try_into().expect("a, b should be small enough to fit into i32.");
+use std::sync::OnceLock;
+use unicode_categories::UnicodeCategories;
+use crate::{cantonese, common, data, reverse_lookup};
+
+/// Extra cost of an odd character in UnigramModel, on top of the cost of the rarest possible word.
+/// Odd characters should only be used when there's no way to cover a character with words.
+pub const ODD_CHAR_PENALTY : f32 = 10.0;
+
+/// Count given to words that were never seen in UnigramModel::from_counts.
+const UNSEEN_WORD_COUNT : f64 = 0.5;
+
+/// Scores the segments of a segmentation. The segmentation with the lowest total cost is chosen, so
+/// costs are usually negative log probabilities (see UnigramModel), but any non-negative costs can
+/// be used.
+pub trait SegmentationModel {
+    /// The cost of using the string as a word, or None if it's not a word.
+    fn word_cost(&self, word: &str) -> Option<f32>;
+
+    /// The cost of a character that is not part of any word (an "odd" character).
+    fn odd_cost(&self, ch: char) -> f32;
+
+    /// The words at the start of chars, as (length in characters, cost), from the shortest to the
+    /// longest. The default implementation looks up every prefix with word_cost, which takes
+    /// quadratic time over the whole string, so models should override it if they can (e.g. with
+    /// a trie).
+    fn prefix_words(&self, chars: &[char]) -> Vec<(usize, f32)> {
+        let mut word = String::new();
+        let mut result = vec![];
+        for (i, ch) in chars.iter().enumerate() {
+            word.push(*ch);
+            if let Some(cost) = self.word_cost(&word) {
+                result.push((i + 1, cost));
             }
         }
+        result
     }
+}
 
-    dp.insert((a, b), res);
-    bt.insert((a, b), bt_res);
-    res
+/// A prefix tree of words with their costs, for finding all the words that a string starts with.
+#[derive(Clone, Default)]
+pub struct WordTrie {
+    /// The children of each node, sorted by the character. Node 0 is the root.
+    children: Vec<Vec<(char, u32)>>,
+    costs: Vec<Option<f32>>,
 }
 
-/// Backtracking function.
-/// Returns a tuple containing: tuples of odd character indices that are not
-/// matched, and indices of the segments (segment's start index).
-fn _bt(
-    a: usize,
-    b: usize,
-    bt: &HashMap<(usize, usize), i32>,
-) -> (Vec<usize>, Vec<usize>) {
-    if bt[&(a, b)] == BT_MATCH {
-        return (vec![], vec![a]);
+impl WordTrie {
+    pub fn new() -> Self {
+        WordTrie { children: vec![vec![]], costs: vec![None] }
     }
 
-    if bt[&(a, b)] == BT_ODD {
-        return (vec![a], vec![a]);
+    /// Adds the word with the cost, replacing the cost if the word is already in the trie.
+    pub fn insert(&mut self, word: &str, cost: f32) {
+        let mut node = 0;
+        for ch in word.chars() {
+            node = match self.children[node].binary_search_by_key(&ch, |(c, _)| *c) {
+                Ok(i) => self.children[node][i].1 as usize,
+                Err(i) => {
+                    let child = self.children.len();
+                    self.children[node].insert(i, (ch, child as u32));
+                    self.children.push(vec![]);
+                    self.costs.push(None);
+                    child
+                },
+            };
+        }
+        self.costs[node] = Some(cost);
     }
 
-    let mid = bt[&(a, b)] as usize;
-    let (left_odd, left_seg) = _bt(a, mid, bt);
-    let (right_odd, right_seg) = _bt(mid, b, bt);
+    fn child(&self, node: usize, ch: char) -> Option<usize> {
+        let children = &self.children[node];
+        children.binary_search_by_key(&ch, |(c, _)| *c).ok().map(|i| children[i].1 as usize)
+    }
 
-    let mut odd = left_odd;
-    odd.extend(right_odd);
+    /// Removes the word from the trie, if it's there.
+    pub fn remove(&mut self, word: &str) {
+        let mut node = 0;
+        for ch in word.chars() {
+            match self.child(node, ch) {
+                Some(child) => node = child,
+                None => return,
+            }
+        }
+        self.costs[node] = None;
+    }
 
-    let mut seg = left_seg;
-    seg.extend(right_seg);
+    /// The lowest cost of any word in the trie, or None if the trie is empty.
+    pub fn min_cost(&self) -> Option<f32> {
+        self.costs.iter().flatten().copied().reduce(f32::min)
+    }
 
-    (odd, seg)
+    /// The cost of the word, or None if it's not in the trie.
+    pub fn get(&self, word: &str) -> Option<f32> {
+        let mut node = 0;
+        for ch in word.chars() {
+            node = self.child(node, ch)?;
+        }
+        self.costs[node]
+    }
+
+    /// The words in the trie that chars starts with, as (length in characters, cost), from the
+    /// shortest to the longest.
+    pub fn prefixes(&self, chars: &[char]) -> Vec<(usize, f32)> {
+        let mut result = vec![];
+        let mut node = 0;
+        for (i, ch) in chars.iter().enumerate() {
+            match self.child(node, *ch) {
+                Some(child) => node = child,
+                None => break,
+            }
+            if let Some(cost) = self.costs[node] {
+                result.push((i + 1, cost));
+            }
+        }
+        result
+    }
 }
 
-/// Returns two lists as a pair: The first list contains indices of unmatched odd characters. The
-/// second list contains the indices of the segments (the segment's start index).
-pub fn segment_with_dictionary(phrase: &str, dictionary: Option<&HashSet<String>>) -> (Vec<usize>, Vec<usize>) {
-    let chars : Vec<char> = phrase.chars().collect();
-    let n = chars.len();
+/// The original model of segment_with_dictionary, which only knows whether a string is a word.
+/// Words cost 1/(length of the word), which prefers longer words, and odd characters cost 10.
+pub struct DictionaryModel<'a> {
+    pub dictionary: &'a HashSet<String>,
+    /// The length of the longest word in the dictionary, in characters
+    max_word_len: usize,
+}
+
+impl<'a> DictionaryModel<'a> {
+    pub fn new(dictionary: &'a HashSet<String>) -> Self {
+        let max_word_len = dictionary.iter().map(|w| w.chars().count()).max().unwrap_or(0);
+        DictionaryModel { dictionary, max_word_len }
+    }
+}
+
+impl SegmentationModel for DictionaryModel<'_> {
+    fn word_cost(&self, word: &str) -> Option<f32> {
+        self.dictionary.contains(word).then(|| 1.0 / word.chars().count() as f32)
+    }
+
+    fn odd_cost(&self, _ch: char) -> f32 {
+        10.0
+    }
+
+    fn prefix_words(&self, chars: &[char]) -> Vec<(usize, f32)> {
+        let mut word = String::new();
+        let mut result = vec![];
+        for (i, ch) in chars.iter().take(self.max_word_len).enumerate() {
+            word.push(*ch);
+            if self.dictionary.contains(&word) {
+                result.push((i + 1, 1.0 / (i + 1) as f32));
+            }
+        }
+        result
+    }
+}
+
+/// Unigram language model, where the cost of a word is its negative log probability, so the most
+/// probable segmentation is chosen.
+#[derive(Clone)]
+pub struct UnigramModel {
+    costs: WordTrie,
+    odd_cost: f32,
+}
+
+impl UnigramModel {
+    /// Builds the model from word counts in a corpus. Words with a count of 0 are given a count of
+    /// 0.5, so they are still words but are less likely than any word that was seen.
+    pub fn from_counts<I: IntoIterator<Item = (String, u64)>>(counts: I) -> Self {
+        let counts : Vec<(String, f64)> = counts.into_iter()
+            .map(|(word, count)| (word, if count == 0 { UNSEEN_WORD_COUNT } else { count as f64 }))
+            .collect();
+        let total : f64 = counts.iter().map(|(_, count)| count).sum::<f64>().max(1.0);
+        let mut costs = WordTrie::new();
+        for (word, count) in counts {
+            costs.insert(&word, (total / count).ln() as f32);
+        }
+        UnigramModel { costs, odd_cost: (total / UNSEEN_WORD_COUNT).ln() as f32 + ODD_CHAR_PENALTY }
+    }
+
+    /// Builds the model from word log probabilities (natural logarithms). Odd characters cost
+    /// odd_cost.
+    pub fn from_log_probs<I: IntoIterator<Item = (String, f32)>>(log_probs: I, odd_cost: f32) -> Self {
+        let mut costs = WordTrie::new();
+        for (word, lp) in log_probs {
+            costs.insert(&word, -lp);
+        }
+        UnigramModel { costs, odd_cost }
+    }
+}
+
+impl SegmentationModel for UnigramModel {
+    fn word_cost(&self, word: &str) -> Option<f32> {
+        self.costs.get(word)
+    }
+
+    fn odd_cost(&self, _ch: char) -> f32 {
+        self.odd_cost
+    }
+
+    fn prefix_words(&self, chars: &[char]) -> Vec<(usize, f32)> {
+        self.costs.prefixes(chars)
+    }
+}
+
+/// The default model for segmenting Cantonese, which is a unigram model of the words in wordlist.
+/// Since wordlist doesn't have counts, the count of a word is estimated as the charlist count of
+/// its least common character reading (see reverse_lookup::word_count), using the pronunciation of
+/// the word that gives the highest count.
+pub fn default_unigram_model() -> &'static UnigramModel {
+    static DATA: OnceLock<UnigramModel> = OnceLock::new();
+    DATA.get_or_init(|| {
+        let mut counts : HashMap<String, u64> = data::cantonese_wordlist_with_jyutping().keys().map(|w| (w.clone(), 0)).collect();
+        for (word, _, syllables) in cantonese::parsed_wordlist() {
+            let count = counts.get_mut(*word).expect("parsed_wordlist only has words of wordlist");
+            *count = (*count).max(reverse_lookup::word_count(word, syllables));
+        }
+        UnigramModel::from_counts(counts)
+    })
+}
+
+/// A compiled dictionary for segmenting many strings, which can be shared across threads. Unlike
+/// segment_with_dictionary with a dictionary, the words are only compiled once, and user words can
+/// be added to the default word list.
+#[derive(Clone)]
+pub struct Segmenter {
+    words: WordTrie,
+    odd_cost: f32,
+    /// The cost of the words added with add_word, or None to use the cost of DictionaryModel
+    user_word_cost: Option<f32>,
+}
+
+// Segmenters are meant to be shared across threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Segmenter>()
+};
+
+impl Segmenter {
+    /// A segmenter with the words of wordlist, scored with default_unigram_model.
+    pub fn new() -> Self {
+        Segmenter::from(default_unigram_model().clone())
+    }
+
+    /// A segmenter with only the words of the dictionary, scored as in DictionaryModel (i.e. the
+    /// same as segment_with_dictionary with the dictionary).
+    pub fn from_dictionary<'a, I: IntoIterator<Item = &'a str>>(dictionary: I) -> Self {
+        let mut segmenter = Segmenter { words: WordTrie::new(), odd_cost: 10.0, user_word_cost: None };
+        for word in dictionary {
+            segmenter.add_word(word);
+        }
+        segmenter
+    }
+
+    /// Adds a word to the dictionary of the segmenter. If the segmenter uses a unigram model, the
+    /// word is given the cost of the most common word, so that it's preferred over splitting it
+    /// into other words. Otherwise the cost is the same as in DictionaryModel.
+    pub fn add_word(&mut self, word: &str) {
+        if word.is_empty() {
+            return;
+        }
+        let cost = self.user_word_cost.unwrap_or(1.0 / word.chars().count() as f32);
+        self.words.insert(word, cost);
+    }
+
+    /// Same as add_word, but the word is weight times as likely, so a weight above 1 makes the word
+    /// preferred over other segmentations and a weight below 1 makes it less preferred. Weights
+    /// must be positive. The cost can't go below 0 (see SegmentationModel), so all weights above
+    /// e^cost have the same effect. With a dictionary scored as in DictionaryModel, the cost is
+    /// divided by the weight instead.
+    pub fn add_word_with_weight(&mut self, word: &str, weight: f32) {
+        if word.is_empty() {
+            return;
+        }
+        let cost = match self.user_word_cost {
+            Some(cost) => (cost - weight.ln()).max(0.0),
+            None => 1.0 / (word.chars().count() as f32 * weight),
+        };
+        self.words.insert(word, cost);
+    }
+
+    /// Removes a word from the dictionary of the segmenter, e.g. a word of wordlist that should
+    /// never be used.
+    pub fn remove_word(&mut self, word: &str) {
+        self.words.remove(word);
+    }
+
+    /// Same as add_word, but for many words, and returns the segmenter.
+    pub fn with_words<'a, I: IntoIterator<Item = &'a str>>(mut self, words: I) -> Self {
+        for word in words {
+            self.add_word(word);
+        }
+        self
+    }
+
+    /// Same as segment_with_dictionary, but with the dictionary of the segmenter.
+    pub fn segment(&self, phrase: &str) -> (Vec<usize>, Vec<usize>) {
+        segment_with_model(phrase, self)
+    }
+
+    /// Same as end_user_friendly_segment, but with the dictionary of the segmenter.
+    pub fn end_user_friendly_segment(&self, s: &str) -> (Vec<char>, Vec<char>, Vec<String>) {
+        end_user_friendly_segment_with_model(s, self)
+    }
+
+    /// Same as segment_text, but with the dictionary of the segmenter.
+    pub fn segment_text(&self, s: &str) -> Segmentation {
+        segment_text_with_model(s, self)
+    }
+
+    /// Same as segment_tokens, but with the dictionary of the segmenter.
+    pub fn segment_tokens(&self, phrase: &str) -> Vec<(String, TokenKind)> {
+        segment_tokens_with_model(phrase, self)
+    }
+
+    /// Same as segment_n_best, but with the dictionary of the segmenter.
+    pub fn n_best(&self, phrase: &str, n: usize) -> Vec<(f32, Vec<usize>, Vec<usize>)> {
+        segment_n_best_with_model(phrase, n, self)
+    }
+
+    /// Same as word_lattice, but with the dictionary of the segmenter.
+    pub fn word_lattice(&self, phrase: &str) -> Vec<(usize, usize, f32)> {
+        word_lattice_with_model(phrase, self)
+    }
+}
+
+impl Default for Segmenter {
+    fn default() -> Self {
+        Segmenter::new()
+    }
+}
 
-    if n == 0 {
-        return (vec![], vec![]);
+impl From<UnigramModel> for Segmenter {
+    fn from(model: UnigramModel) -> Self {
+        let user_word_cost = Some(model.costs.min_cost().unwrap_or(0.0));
+        Segmenter { words: model.costs, odd_cost: model.odd_cost, user_word_cost }
     }
+}
+
+impl SegmentationModel for Segmenter {
+    fn word_cost(&self, word: &str) -> Option<f32> {
+        self.words.get(word)
+    }
+
+    fn odd_cost(&self, _ch: char) -> f32 {
+        self.odd_cost
+    }
+
+    fn prefix_words(&self, chars: &[char]) -> Vec<(usize, f32)> {
+        self.words.prefixes(chars)
+    }
+}
+
+/// The segmenter with the words of wordlist (see Segmenter::new), which is used when no dictionary
+/// is given.
+pub fn default_segmenter() -> &'static Segmenter {
+    static DATA: OnceLock<Segmenter> = OnceLock::new();
+    DATA.get_or_init(Segmenter::new)
+}
 
-    let mut dp: HashMap<(usize, usize), f32> = HashMap::new();
-    let mut bt: HashMap<(usize, usize), i32> = HashMap::new();
+/// The kind of a token (segment) of a segmentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// A word of the dictionary, which can also have Latin letters (e.g. "book枱").
+    Word,
+    /// A character that is not part of any word.
+    Odd,
+    /// A word in Latin letters that is not in the dictionary, e.g. "boss".
+    Latin,
+    /// A number, e.g. "2024" or "3.14".
+    Digits,
+    /// A URL, e.g. "https://words.hk".
+    Url,
+    /// One or more emoji, e.g. "😂".
+    Emoji,
+    /// Punctuation or symbols, e.g. "，" or "...".
+    Punctuation,
+    /// Spaces and line breaks.
+    Whitespace,
+}
 
-    if let Some(dictionary) = dictionary {
-        _dp(0, n, &chars, dictionary, &mut dp, &mut bt);
-    } else {
-        let dictionary : HashSet<String> = data::cantonese_wordlist_with_jyutping().keys().cloned().collect();
-        _dp(0, n, &chars, &dictionary, &mut dp, &mut bt);
+impl TokenKind {
+    /// The name of the kind, e.g. "word" or "latin".
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Word => "word",
+            TokenKind::Odd => "odd",
+            TokenKind::Latin => "latin",
+            TokenKind::Digits => "digits",
+            TokenKind::Url => "url",
+            TokenKind::Emoji => "emoji",
+            TokenKind::Punctuation => "punctuation",
+            TokenKind::Whitespace => "whitespace",
+        }
     }
-    _bt(0, n, &bt)
 }
 
-// TODO: review this
-fn sequence_filter(x: &[usize]) -> Vec<usize> {
-    let mut ret = Vec::new();
-    for i in 1..x.len() {
-        if x[i - 1] + 1 == x[i] {
-            if ret.is_empty() || *ret.last().unwrap() != x[i - 1] {
-                ret.push(x[i - 1]);
+/// A token of a Segmentation. Offsets are into the segmented string, in characters (start and end)
+/// and in bytes (byte_start and byte_end), so that &s[byte_start..byte_end] is the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// The text of the token.
+    pub text: String,
+    /// The index of the first character of the token.
+    pub start: usize,
+    /// The index after the last character of the token.
+    pub end: usize,
+    /// The byte offset of the first character of the token.
+    pub byte_start: usize,
+    /// The byte offset after the last character of the token.
+    pub byte_end: usize,
+    /// The kind of the token.
+    pub kind: TokenKind,
+}
+
+impl Token {
+    /// Whether the token is a word of the dictionary.
+    pub fn in_dictionary(&self) -> bool {
+        self.kind == TokenKind::Word
+    }
+}
+
+/// The result of segment_text: the tokens, which cover the whole string in order, and the total
+/// cost of the segmentation (see SegmentationModel).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segmentation {
+    /// The tokens of the string.
+    pub tokens: Vec<Token>,
+    /// The total cost of the tokens, so lower is better.
+    pub cost: f32,
+}
+
+impl Segmentation {
+    fn new(s: &str, segments: &[(usize, TokenKind)], cost: f32) -> Self {
+        let mut byte_offsets : Vec<usize> = s.char_indices().map(|(i, _)| i).collect();
+        byte_offsets.push(s.len());
+        let ends = segments.iter().skip(1).map(|(start, _)| *start).chain(std::iter::once(byte_offsets.len() - 1));
+        let tokens = segments.iter().zip(ends).map(|(&(start, kind), end)| {
+            let (byte_start, byte_end) = (byte_offsets[start], byte_offsets[end]);
+            Token { text: s[byte_start..byte_end].to_string(), start, end, byte_start, byte_end, kind }
+        }).collect();
+        Segmentation { tokens, cost }
+    }
+
+    /// The texts of the tokens.
+    pub fn texts(&self) -> Vec<&str> {
+        self.tokens.iter().map(|token| token.text.as_str()).collect()
+    }
+
+    /// The runs of consecutive odd characters, each as a single token of the kind Odd.
+    pub fn odd_runs(&self) -> Vec<Token> {
+        let mut runs : Vec<Token> = vec![];
+        for token in self.tokens.iter().filter(|token| token.kind == TokenKind::Odd) {
+            match runs.last_mut() {
+                Some(run) if run.end == token.start => {
+                    run.text.push_str(&token.text);
+                    run.end = token.end;
+                    run.byte_end = token.byte_end;
+                }
+                _ => runs.push(token.clone()),
             }
-            if ret.is_empty() || *ret.last().unwrap() != x[i] {
-                ret.push(x[i]);
+        }
+        runs
+    }
+
+    /// The runs of two or more consecutive odd characters, which are likely unknown words rather
+    /// than stray characters.
+    pub fn bad_runs(&self) -> Vec<Token> {
+        self.odd_runs().into_iter().filter(|run| run.end - run.start > 1).collect()
+    }
+
+    /// The result in the format of end_user_friendly_segment.
+    pub fn end_user_friendly(&self) -> (Vec<char>, Vec<char>, Vec<String>) {
+        let bad_words = self.bad_runs().iter().flat_map(|run| run.text.chars()).collect();
+        let odd_words = self.odd_runs().iter().flat_map(|run| run.text.chars()).collect();
+        (bad_words, odd_words, self.tokens.iter().map(|token| token.text.clone()).collect())
+    }
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('０'..='９').contains(&c)
+}
+
+/// Characters that are shown as emoji by default (Emoji_Presentation). Other symbols such as ☆
+/// and ✓ are only emoji when followed by U+FE0F (see emoji_len).
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x231A..=0x231B | 0x23E9..=0x23EC | 0x23F0 | 0x23F3 | 0x25FD..=0x25FE | 0x2614..=0x2615
+        | 0x2648..=0x2653 | 0x267F | 0x2693 | 0x26A1 | 0x26AA..=0x26AB | 0x26BD..=0x26BE
+        | 0x26C4..=0x26C5 | 0x26CE | 0x26D4 | 0x26EA | 0x26F2..=0x26F3 | 0x26F5 | 0x26FA | 0x26FD
+        | 0x2705 | 0x270A..=0x270B | 0x2728 | 0x274C | 0x274E | 0x2753..=0x2755 | 0x2757
+        | 0x2795..=0x2797 | 0x27B0 | 0x27BF | 0x2B1B..=0x2B1C | 0x2B50 | 0x2B55
+        | 0x1F004 | 0x1F0CF | 0x1F18E | 0x1F191..=0x1F19A | 0x1F1E6..=0x1F1FF | 0x1F201 | 0x1F21A
+        | 0x1F22F | 0x1F232..=0x1F236 | 0x1F238..=0x1F23A | 0x1F250..=0x1F251 | 0x1F300..=0x1F3FA
+        | 0x1F400..=0x1FAFF)
+}
+
+/// Symbols that can be shown as emoji with U+FE0F, e.g. "☺️" and "❤️".
+fn is_emoji_symbol(c: char) -> bool {
+    matches!(c as u32, 0xA9 | 0xAE | 0x2000..=0x2BFF | 0x3030 | 0x303D | 0x3297 | 0x3299 | 0x1F000..=0x1F2FF)
+}
+
+/// Characters that join emoji into one (variation selectors, zero width joiners, skin tones,
+/// keycaps and tags).
+fn is_emoji_continuation(c: char) -> bool {
+    matches!(c as u32, 0xFE0F | 0x200D | 0x1F3FB..=0x1F3FF | 0x20E3 | 0xE0020..=0xE007F)
+}
+
+/// The length of the run of emoji at the start of chars, or 0 if it doesn't start with an emoji.
+fn emoji_len(chars: &[char]) -> usize {
+    let mut len = 0;
+    while len < chars.len() {
+        let c = chars[len];
+        let presented = chars.get(len + 1) == Some(&'\u{FE0F}') && is_emoji_symbol(c);
+        if !(is_emoji(c) || presented || (len > 0 && is_emoji_continuation(c))) {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+/// The length of the URL at the start of chars, or 0 if there isn't one. Punctuation at the end is
+/// not part of the URL (e.g. "https://words.hk." is "https://words.hk" and ".").
+fn url_len(chars: &[char]) -> usize {
+    let prefix : String = chars.iter().take(8).collect::<String>().to_ascii_lowercase();
+    if !["http://", "https://", "www."].iter().any(|p| prefix.starts_with(p)) {
+        return 0;
+    }
+    let mut len = chars.iter().take_while(|c| c.is_ascii_graphic()).count();
+    while len > 0 && ".,;:!?)'\"".contains(chars[len - 1]) {
+        len -= 1;
+    }
+    len
+}
+
+/// Finds the runs of characters that are tokens of their own, as (start, end, kind): URLs, Latin
+/// words (which can have apostrophes and hyphens, e.g. "McDonald's"), numbers (which can have
+/// decimal points and thousands separators), emoji, whitespace, and punctuation (a run of the same
+/// punctuation mark, e.g. "..."). Other characters (e.g. Chinese characters) are not in any run.
+fn script_runs(chars: &[char]) -> Vec<(usize, usize, TokenKind)> {
+    let n = chars.len();
+    // The end of the run of characters that satisfy f (or of joiners between them) from i
+    let run_end = |mut i: usize, f: &dyn Fn(char) -> bool, joiners: &str| {
+        while i < n && (f(chars[i]) || (joiners.contains(chars[i]) && i + 1 < n && f(chars[i + 1]) && i > 0 && f(chars[i - 1]))) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut runs = vec![];
+    let mut i = 0;
+    while i < n {
+        let c = chars[i];
+        let (end, kind) = if url_len(&chars[i..]) > 0 {
+            (i + url_len(&chars[i..]), TokenKind::Url)
+        } else if common::is_latin_c(c) {
+            (run_end(i, &common::is_latin_c, "'’-"), TokenKind::Latin)
+        } else if is_digit(c) {
+            (run_end(i, &is_digit, ".,"), TokenKind::Digits)
+        } else if emoji_len(&chars[i..]) > 0 {
+            (i + emoji_len(&chars[i..]), TokenKind::Emoji)
+        } else if c.is_whitespace() {
+            (run_end(i, &char::is_whitespace, ""), TokenKind::Whitespace)
+        } else if (c.is_punctuation() || c.is_symbol()) && !common::is_cjk_cp(c as u32) {
+            (run_end(i, &|d| d == c, ""), TokenKind::Punctuation)
+        } else {
+            i += 1;
+            continue;
+        };
+        runs.push((i, end, kind));
+        i = end;
+    }
+    runs
+}
+
+/// A path of the search in k_best, which ends at some position of the string, as (total cost, start
+/// of the last segment, rank of the path that it extends at that start, kind of the last segment).
+type Path = (f32, usize, usize, TokenKind);
+
+/// Finds the k segmentations with the lowest total costs with a forward (Viterbi) pass over the
+/// characters, which keeps the k best paths that end at each position. Runs of characters that are
+/// tokens of their own (see script_runs) are never split, so words can only start and end at
+/// their boundaries, and a run that is not covered by words is a single token with the cost of an
+/// odd character. Other single characters are odd if they are not words. When there's a tie, the
+/// segmentation whose last segment starts later comes first, which keeps longer words at the
+/// start. Returns (total cost, [(start index, kind)] of the segments) from the best to the worst.
+fn k_best(chars: &[char], model: &dyn SegmentationModel, k: usize) -> Vec<(f32, Vec<(usize, TokenKind)>)> {
+    let n = chars.len();
+    if k == 0 {
+        return vec![];
+    }
+
+    // Whether a segment can start or end at each position, and the run that starts there
+    let mut boundary = vec![true; n + 1];
+    let mut run_at = vec![None; n];
+    for (start, end, kind) in script_runs(chars) {
+        boundary[start + 1..end].fill(false);
+        run_at[start] = Some((end - start, kind));
+    }
+
+    let mut paths : Vec<Vec<Path>> = vec![vec![]; n + 1];
+    paths[0].push((0.0, 0, 0, TokenKind::Word));
+
+    for i in (0..n).filter(|i| boundary[*i]) {
+        let words : Vec<(usize, f32)> = model.prefix_words(&chars[i..]).into_iter().filter(|(len, _)| boundary[i + len]).collect();
+        let single = match run_at[i] {
+            // A word with the same span as the run replaces it, so that no two paths have the
+            // same boundaries
+            Some((len, _)) if words.iter().any(|(l, _)| *l == len) => None,
+            Some((len, kind)) => Some((len, model.odd_cost(chars[i]), kind)),
+            None if words.first().map(|(len, _)| *len) != Some(1) => Some((1, model.odd_cost(chars[i]), TokenKind::Odd)),
+            None => None,
+        };
+        let (done, rest) = paths.split_at_mut(i + 1);
+        for (len, cost, kind) in single.into_iter().chain(words.into_iter().map(|(len, cost)| (len, cost, TokenKind::Word))) {
+            let ends = &mut rest[len - 1];
+            for (rank, (total, _, _, _)) in done[i].iter().enumerate() {
+                let total = total + cost;
+                let pos = ends.partition_point(|(t, start, _, _)| *t < total || (*t == total && *start >= i));
+                if pos >= k {
+                    // The other paths at i are not better
+                    break;
+                }
+                ends.insert(pos, (total, i, rank, kind));
+                ends.truncate(k);
             }
         }
     }
-    ret
+
+    paths[n].iter().map(|&(total, mut start, mut rank, mut kind)| {
+        let mut segments = vec![];
+        let mut j = n;
+        while j > 0 {
+            segments.push((start, kind));
+            j = start;
+            (_, start, rank, kind) = paths[j][rank];
+        }
+        segments.reverse();
+        (total, segments)
+    }).collect()
 }
 
+/// Converts segments as (start index, kind) to the format of segment_with_dictionary.
+fn odd_and_segments(segments: &[(usize, TokenKind)]) -> (Vec<usize>, Vec<usize>) {
+    let odd = segments.iter().filter(|(_, kind)| *kind == TokenKind::Odd).map(|(start, _)| *start).collect();
+    (odd, segments.iter().map(|(start, _)| *start).collect())
+}
 
-/// Returns a user-friendly segmentation result for text-based programs. If dictionary is None, we
-/// will load an out-of-date Cantonese dictionary from words.hk
-pub fn end_user_friendly_segment(s: &str, dictionary: Option<&HashSet<String>>) -> (Vec<char>, Vec<char>, Vec<String>) {
-    let (odd_idx, segment_idx) = segment_with_dictionary(s, dictionary);
-    let s_chars: Vec<char> = s.chars().collect();
+/// Returns two lists as a pair: The first list contains indices of unmatched odd characters. The
+/// second list contains the indices of the segments (the segment's start index). Latin words,
+/// numbers, URLs, emoji, punctuation and whitespace that are not part of dictionary words are
+/// segments of their own and are not odd (see segment_text). If dictionary is None, the default
+/// segmenter (see default_segmenter) is used, and otherwise the words of the dictionary are scored
+/// with DictionaryModel. Use a Segmenter to segment many strings with the same dictionary.
+pub fn segment_with_dictionary(phrase: &str, dictionary: Option<&HashSet<String>>) -> (Vec<usize>, Vec<usize>) {
+    match dictionary {
+        Some(dictionary) => segment_with_model(phrase, &DictionaryModel::new(dictionary)),
+        None => default_segmenter().segment(phrase),
+    }
+}
 
-    let bad_words: Vec<char> = sequence_filter(&odd_idx).iter().map(|&idx| s_chars[idx]).collect();
-    let odd_words: Vec<char> = odd_idx.iter().map(|&idx| s_chars[idx]).collect();
-    let mut segmentation: Vec<String> = Vec::new();
+/// Same as segment_with_dictionary, but the segments are scored with the model.
+pub fn segment_with_model(phrase: &str, model: &dyn SegmentationModel) -> (Vec<usize>, Vec<usize>) {
+    let tokens = segment_text_with_model(phrase, model).tokens;
+    let odd = tokens.iter().filter(|token| token.kind == TokenKind::Odd).map(|token| token.start).collect();
+    (odd, tokens.iter().map(|token| token.start).collect())
+}
+
+/// Segments the string into typed tokens with their offsets (see Segmentation). If dictionary is
+/// None, the default segmenter (see default_segmenter) is used, and otherwise the words of the
+/// dictionary are scored with DictionaryModel. Latin words, numbers, URLs, emoji, punctuation and
+/// whitespace that are not part of dictionary words are tokens of their own (see TokenKind), and
+/// other characters that are not part of words are odd.
+pub fn segment_text(s: &str, dictionary: Option<&HashSet<String>>) -> Segmentation {
+    match dictionary {
+        Some(dictionary) => segment_text_with_model(s, &DictionaryModel::new(dictionary)),
+        None => segment_text_with_model(s, default_segmenter()),
+    }
+}
+
+/// Same as segment_text, but the segments are scored with the model.
+pub fn segment_text_with_model(s: &str, model: &dyn SegmentationModel) -> Segmentation {
+    let chars : Vec<char> = s.chars().collect();
+    let (cost, segments) = k_best(&chars, model, 1).pop().expect("every string has a segmentation");
+    Segmentation::new(s, &segments, cost)
+}
+
+/// Gets the n best segmentations of the phrase, from the best to the worst, as (cost, indices of
+/// unmatched odd characters, indices of the segments). The first one is the same as
+/// segment_with_dictionary. The cost is the total cost of the segments (see SegmentationModel), so
+/// lower is better. Fewer than n segmentations are returned if there aren't enough of them.
+pub fn segment_n_best(phrase: &str, n: usize, dictionary: Option<&HashSet<String>>) -> Vec<(f32, Vec<usize>, Vec<usize>)> {
+    match dictionary {
+        Some(dictionary) => segment_n_best_with_model(phrase, n, &DictionaryModel::new(dictionary)),
+        None => segment_n_best_with_model(phrase, n, default_segmenter()),
+    }
+}
+
+/// Same as segment_n_best, but the segments are scored with the model.
+pub fn segment_n_best_with_model(phrase: &str, n: usize, model: &dyn SegmentationModel) -> Vec<(f32, Vec<usize>, Vec<usize>)> {
+    let chars : Vec<char> = phrase.chars().collect();
+    k_best(&chars, model, n).into_iter().map(|(cost, segments)| {
+        let (odd, seg) = odd_and_segments(&segments);
+        (cost, odd, seg)
+    }).collect()
+}
 
-    for window in segment_idx.windows(2) {
-        segmentation.push(s_chars[window[0]..window[1]].iter().collect());
+/// Segments the phrase into typed tokens, as (token, kind). Same as segment_text without the
+/// offsets.
+pub fn segment_tokens(phrase: &str, dictionary: Option<&HashSet<String>>) -> Vec<(String, TokenKind)> {
+    match dictionary {
+        Some(dictionary) => segment_tokens_with_model(phrase, &DictionaryModel::new(dictionary)),
+        None => segment_tokens_with_model(phrase, default_segmenter()),
     }
-    if let Some(&last) = segment_idx.last() {
-        segmentation.push(s_chars[last..].iter().collect());
+}
+
+/// Same as segment_tokens, but the segments are scored with the model.
+pub fn segment_tokens_with_model(phrase: &str, model: &dyn SegmentationModel) -> Vec<(String, TokenKind)> {
+    segment_text_with_model(phrase, model).tokens.into_iter().map(|token| (token.text, token.kind)).collect()
+}
+
+/// Gets the word lattice of the phrase, i.e. every word of the dictionary in the phrase, as (start
+/// index, end index, cost), sorted by the start and then the end. Indices are in characters. See
+/// segment_with_dictionary for the dictionary.
+pub fn word_lattice(phrase: &str, dictionary: Option<&HashSet<String>>) -> Vec<(usize, usize, f32)> {
+    match dictionary {
+        Some(dictionary) => word_lattice_with_model(phrase, &DictionaryModel::new(dictionary)),
+        None => word_lattice_with_model(phrase, default_segmenter()),
     }
+}
 
-    (bad_words, odd_words, segmentation)
+/// Same as word_lattice, but with the words and costs of the model.
+pub fn word_lattice_with_model(phrase: &str, model: &dyn SegmentationModel) -> Vec<(usize, usize, f32)> {
+    let chars : Vec<char> = phrase.chars().collect();
+    (0..chars.len())
+        .flat_map(|i| model.prefix_words(&chars[i..]).into_iter().map(move |(len, cost)| (i, i + len, cost)))
+        .collect()
 }
 
+/// Returns a user-friendly segmentation result for text-based programs, as (characters in runs of
+/// two or more odd characters, odd characters, segments). Latin words, numbers, URLs, emoji,
+/// punctuation and whitespace are segments of their own and are not odd, so e.g. "boss" is one
+/// segment. If dictionary is None, the default segmenter (see default_segmenter) is used. Use
+/// segment_text for the offsets and kinds of the segments.
+pub fn end_user_friendly_segment(s: &str, dictionary: Option<&HashSet<String>>) -> (Vec<char>, Vec<char>, Vec<String>) {
+    segment_text(s, dictionary).end_user_friendly()
+}
+
+/// Same as end_user_friendly_segment, but the segments are scored with the model.
+pub fn end_user_friendly_segment_with_model(s: &str, model: &dyn SegmentationModel) -> (Vec<char>, Vec<char>, Vec<String>) {
+    segment_text_with_model(s, model).end_user_friendly()
+}
@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+use crate::{cantonese, segmentation};
+
+/// An entry of a UserDictionary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserEntry {
+    /// How much more likely the word is than a plain user word (see
+    /// segmentation::Segmenter::add_word_with_weight), or None for a plain user word.
+    pub weight: Option<f32>,
+    /// The pronunciation used by get_ping3jam1 instead of the one in wordlist, as canonical
+    /// Jyutping (see cantonese::canonicalize_jyutping).
+    pub pronunciation: Option<String>,
+}
+
+/// A layer of a LayeredDictionary: words to add with their weights and pronunciations, and words
+/// to suppress.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserDictionary {
+    pub entries: HashMap<String, UserEntry>,
+    /// Words that are removed from the layers below, including wordlist.
+    pub blacklist: HashSet<String>,
+}
+
+impl UserDictionary {
+    pub fn new() -> Self {
+        UserDictionary::default()
+    }
+
+    /// Adds the word, replacing any entry of the word and removing it from the blacklist.
+    pub fn add(&mut self, word: &str, entry: UserEntry) {
+        self.blacklist.remove(word);
+        self.entries.insert(word.to_string(), entry);
+    }
+
+    /// Adds the word to the blacklist, removing any entry of the word.
+    pub fn block(&mut self, word: &str) {
+        self.entries.remove(word);
+        self.blacklist.insert(word.to_string());
+    }
+
+    /// Parses a dictionary in TSV format. Every line is a word, optionally followed by its weight
+    /// and its pronunciation in Jyutping, separated by tabs, e.g. "分詞器\t2\tfan1 ci4 hei3". Either
+    /// can be left empty. A word that starts with "-" (e.g. "-研究生") is added to the blacklist.
+    /// Empty lines and lines that start with "#" are ignored. Later lines replace earlier ones.
+    pub fn from_tsv(s: &str) -> Result<Self, String> {
+        let mut dictionary = UserDictionary::new();
+        for (i, line) in s.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}: {}", i + 1, message, line);
+            let fields : Vec<&str> = line.split('\t').map(|field| field.trim()).collect();
+            if fields.len() > 3 {
+                return Err(error("too many fields"));
+            }
+            if let Some(word) = fields[0].strip_prefix('-').filter(|word| !word.is_empty()) {
+                if fields.iter().skip(1).any(|field| !field.is_empty()) {
+                    return Err(error("a blacklisted word can't have a weight or a pronunciation"));
+                }
+                dictionary.block(word);
+                continue;
+            }
+            if fields[0].is_empty() {
+                return Err(error("empty word"));
+            }
+            let weight = match fields.get(1).filter(|field| !field.is_empty()) {
+                Some(field) => match field.parse::<f32>() {
+                    Ok(weight) if weight > 0.0 && weight.is_finite() => Some(weight),
+                    _ => return Err(error("the weight must be a positive number")),
+                },
+                None => None,
+            };
+            let pronunciation = match fields.get(2).filter(|field| !field.is_empty()) {
+                Some(field) => match cantonese::canonicalize_jyutping(field, true) {
+                    Some(pronunciation) => Some(pronunciation),
+                    None => return Err(error("invalid Jyutping")),
+                },
+                None => None,
+            };
+            dictionary.add(fields[0], UserEntry { weight, pronunciation });
+        }
+        Ok(dictionary)
+    }
+
+    /// Reads a dictionary in TSV format (see from_tsv) from the file.
+    pub fn load_tsv(path: &str) -> std::io::Result<Self> {
+        let s = std::fs::read_to_string(path)?;
+        Self::from_tsv(&s).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+    }
+}
+
+/// A dictionary made of layers on top of a base dictionary (by default, wordlist). Each layer adds
+/// words and suppresses words of the layers below it, so e.g. a project can have its own words on
+/// top of wordlist, and a document can have its own words on top of those. The layers are compiled
+/// into a segmentation::Segmenter, and the dictionary can be shared across threads (e.g. in an
+/// Arc) once all layers are added.
+#[derive(Clone)]
+pub struct LayeredDictionary {
+    segmenter: segmentation::Segmenter,
+    layers: Vec<UserDictionary>,
+    /// The forced pronunciation of each word, from the top layer that has the word
+    pronunciations: HashMap<String, String>,
+}
+
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<LayeredDictionary>()
+};
+
+impl LayeredDictionary {
+    /// A dictionary with the words of wordlist as the base (see segmentation::Segmenter::new).
+    pub fn new() -> Self {
+        Self::with_base(segmentation::Segmenter::new())
+    }
+
+    /// A dictionary with the words of the segmenter as the base.
+    pub fn with_base(segmenter: segmentation::Segmenter) -> Self {
+        LayeredDictionary { segmenter, layers: vec![], pronunciations: HashMap::new() }
+    }
+
+    /// Adds a layer on top of the others.
+    pub fn push_layer(&mut self, layer: UserDictionary) {
+        for word in &layer.blacklist {
+            self.segmenter.remove_word(word);
+            self.pronunciations.remove(word);
+        }
+        for (word, entry) in &layer.entries {
+            match entry.weight {
+                Some(weight) => self.segmenter.add_word_with_weight(word, weight),
+                None => self.segmenter.add_word(word),
+            }
+            match &entry.pronunciation {
+                Some(pronunciation) => self.pronunciations.insert(word.clone(), pronunciation.clone()),
+                None => self.pronunciations.remove(word),
+            };
+        }
+        self.layers.push(layer);
+    }
+
+    /// Same as push_layer, but returns the dictionary.
+    pub fn with_layer(mut self, layer: UserDictionary) -> Self {
+        self.push_layer(layer);
+        self
+    }
+
+    /// The layers, from the bottom to the top.
+    pub fn layers(&self) -> &[UserDictionary] {
+        &self.layers
+    }
+
+    /// The compiled segmenter of all the layers.
+    pub fn segmenter(&self) -> &segmentation::Segmenter {
+        &self.segmenter
+    }
+
+    /// The forced pronunciation of the word, if a layer has one for it.
+    pub fn pronunciation(&self, word: &str) -> Option<&str> {
+        self.pronunciations.get(word).map(|p| p.as_str())
+    }
+
+    /// Same as cantonese::get_ping3jam1, but with the words and pronunciations of the dictionary.
+    pub fn get_ping3jam1(&self, s: &str) -> String {
+        cantonese::get_ping3jam1_with_dictionary(s, self)
+    }
+}
+
+impl Default for LayeredDictionary {
+    fn default() -> Self {
+        LayeredDictionary::new()
+    }
+}
@@ -0,0 +1,153 @@
+/*!
+Conversion of character variants into the forms that words.hk considers canonical. The maps are
+generated by `zigen` (see the Makefile) and loaded through `data::initialize_data`.
+*/
+
+use crate::common;
+use crate::data;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Converts the characters in the string to their words.hk canonical forms, using only the "safe"
+/// autoconvert map. This is what words.hk applies automatically to user input.
+pub fn hk_variant_autoconvert(s: &str) -> String {
+    let autoconvert = data::wordshk_autoconvert();
+    s.chars().map(|c| *autoconvert.get(&c).unwrap_or(&c)).collect()
+}
+
+/// Converts the characters in the string to their words.hk canonical forms, using the full variant
+/// map in addition to the autoconvert map. Characters that are already in the canonical character
+/// set are never converted, even if the variant map has an entry for them, since such conversions
+/// are lossy (e.g. 著 => 着).
+pub fn hk_variant(s: &str) -> String {
+    let autoconvert = data::wordshk_autoconvert();
+    let variantmap = data::wordshk_variantmap();
+    let charset = data::wordshk_charset();
+    s.chars().map(|c| {
+        if let Some(to) = autoconvert.get(&c) {
+            *to
+        } else if charset.contains(&c) {
+            c
+        } else {
+            *variantmap.get(&c).unwrap_or(&c)
+        }
+    }).collect()
+}
+
+/// Returns the CJK characters in the string that are not in the words.hk canonical character set,
+/// as a list of (char offset, character, suggested canonical replacement). The suggestion comes
+/// from the variant maps and is None if we don't know of a canonical form. Non-CJK characters are
+/// not checked.
+pub fn conformant(s: &str) -> Vec<(usize, char, Option<char>)> {
+    let autoconvert = data::wordshk_autoconvert();
+    let variantmap = data::wordshk_variantmap();
+    let charset = data::wordshk_charset();
+    s.chars().enumerate()
+        .filter(|(_, c)| common::is_cjk_cp(*c as u32) && !charset.contains(c))
+        .map(|(i, c)| (i, c, autoconvert.get(&c).or(variantmap.get(&c)).copied()))
+        .collect()
+}
+
+/// Maximum number of results returned by `generate_variants`. The number of combinations grows
+/// exponentially with the length of the input, so we need to stop somewhere.
+pub const GENERATE_VARIANTS_MAX : usize = 16;
+
+/// Reverses a map of (variant character) => (canonical character). The variants are sorted by code
+/// point so that the generated results are stable.
+fn reverse_map(map: &HashMap<char, char>) -> HashMap<char, String> {
+    let mut reversed : HashMap<char, Vec<char>> = HashMap::new();
+    for (fr, to) in map {
+        reversed.entry(*to).or_default().push(*fr);
+    }
+    reversed.into_iter().map(|(k, mut v)| { v.sort(); (k, v.into_iter().collect()) }).collect()
+}
+
+/// A map of (words.hk canonical character) => (variant characters), i.e. the reverse of the full
+/// variant map. Note that it has many rare variants (e.g. Extension B characters), which are
+/// seldom useful as search aliases.
+pub fn wordshk_reverse_variantmap() -> &'static HashMap<char, String> {
+    static DATA: OnceLock<HashMap<char, String>> = OnceLock::new();
+    DATA.get_or_init(|| reverse_map(data::wordshk_variantmap()))
+}
+
+/// A map of (words.hk canonical character) => (variant characters), i.e. the reverse of the
+/// autoconvert map, which only has common variants.
+pub fn wordshk_reverse_autoconvert() -> &'static HashMap<char, String> {
+    static DATA: OnceLock<HashMap<char, String>> = OnceLock::new();
+    DATA.get_or_init(|| reverse_map(data::wordshk_autoconvert()))
+}
+
+/// Lazy iterator over the variant spellings of a string. See `generate_variants`.
+pub struct VariantIterator {
+    // Each unit is a run of identical characters, with the list of characters it can be spelt as
+    // (the original character first) and the length of the run.
+    units: Vec<(Vec<char>, usize)>,
+    // The current choice for each unit, as an index into the unit's list of characters
+    choices: Vec<usize>,
+    done: bool,
+}
+
+impl VariantIterator {
+    fn new(s: &str, reverse_map: &HashMap<char, String>) -> Self {
+        let mut units : Vec<(Vec<char>, usize)> = Vec::new();
+        for c in s.chars() {
+            match units.last_mut() {
+                Some((options, run)) if options[0] == c => { *run += 1; },
+                _ => {
+                    let mut options = vec![c];
+                    if let Some(variants) = reverse_map.get(&c) {
+                        options.extend(variants.chars().filter(|v| *v != c));
+                    }
+                    units.push((options, 1));
+                }
+            }
+        }
+        let choices = vec![0; units.len()];
+        VariantIterator { units, choices, done: false }
+    }
+}
+
+impl Iterator for VariantIterator {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.done {
+            return None;
+        }
+
+        // Increment the choices like an odometer, with the last unit changing the fastest. The
+        // all-original spelling is the starting point and is never returned.
+        let mut i = self.units.len();
+        loop {
+            if i == 0 {
+                self.done = true;
+                return None;
+            }
+            i -= 1;
+            self.choices[i] += 1;
+            if self.choices[i] < self.units[i].0.len() {
+                break;
+            }
+            self.choices[i] = 0;
+        }
+
+        Some(self.units.iter().zip(self.choices.iter())
+            .flat_map(|((options, run), &choice)| std::iter::repeat_n(options[choice], *run))
+            .collect())
+    }
+}
+
+/// Lazily generates spellings of the string where characters are swapped for their variants.
+/// Unlike `generate_variants`, the number of results is not capped.
+pub fn generate_variants_iter(s: &str, reverse_map: Option<&HashMap<char, String>>) -> VariantIterator {
+    VariantIterator::new(s, reverse_map.unwrap_or_else(|| wordshk_reverse_variantmap()))
+}
+
+/// Generates spellings of the string where characters are swapped for their variants, e.g. to be
+/// used as search aliases. reverse_map maps a character to its variants, and defaults to
+/// wordshk_reverse_variantmap (pass wordshk_reverse_autoconvert for common variants only). Runs
+/// of identical characters are always spelt the same way (so 一一 gives ①① but not 一①). The
+/// original string is not included, and at most GENERATE_VARIANTS_MAX results are returned.
+pub fn generate_variants(s: &str, reverse_map: Option<&HashMap<char, String>>) -> Vec<String> {
+    generate_variants_iter(s, reverse_map).take(GENERATE_VARIANTS_MAX).collect()
+}
@@ -11,15 +11,65 @@ import re
 def join_space(s: str) -> str:
     return re.sub(r"\s+", " ", s, re.DOTALL).strip()
 
+def split_args(argstr: str) -> list:
+    """Splits the arguments on the commas that are not inside brackets, e.g. of HashMap<K, V>."""
+    args = [""]
+    depth = 0
+    for c in argstr:
+        if c in "<([":
+            depth += 1
+        elif c in ">)]":
+            depth -= 1
+        if c == "," and depth == 0:
+            args.append("")
+        else:
+            args[-1] += c
+    return args
+
 ignored_functions = set([
     "binary_search_file",
+    "careful_speech_candidates",
+    "careful_syllables",
+    "changed_tone_base",
+    "chars_with_pronunciation",
+    "cantonese_pinyin_to_syllable",
+    "default_segmenter",
+    "default_unigram_model",
+    "discover_words",
+    "end_user_friendly_segment_with_model",
+    "find_lazy_sound_entries",
+    "fuzzy_search",
+    "generate_variants_iter",
+    "get_ping3jam1_spans",
+    "get_ping3jam1_with_dictionary",
+    "get_ping3jam1_syllables",
+    "get_ping3jam1_syllables_from_charlist",
+    "jyutping_to_ipa",
     "jyutping_validator",
+    "normalize_text_pieces_with_model",
     "radical_char_cmp",
     "radical_cmp",
     "radical_label_to_chars",
+    "segment_n_best_with_model",
+    "segment_text",
+    "segment_text_with_model",
+    "segment_tokens",
+    "segment_tokens_with_model",
+    "segment_with_model",
+    "sidney_lau_to_syllable",
+    "syllable_to_cantonese_pinyin",
+    "syllable_to_ipa",
+    "syllable_to_sidney_lau",
+    "syllable_to_yale",
+    "word_lattice_with_model",
+    "words_with_pronunciation",
+    "wordshk_reverse_autoconvert",
+    "wordshk_reverse_variantmap",
+    "yale_to_syllable",
     ])
 ignored_rust_files = set([
     "data.rs",
+    "jyutping.rs",
     ])
 # Copy all function definitions from source path to destination file
 def main():
@@ -44,7 +94,7 @@ def main():
         for line in destination_lines[:function_wrappers_start_idx+1]:
             dest.write(line)
 
-        for file in glob.glob(source_directory + "/src/*.rs"):
+        for file in sorted(glob.glob(source_directory + "/src/*.rs")):
             if os.path.basename(file) in ignored_rust_files:
                 continue
             # Remember doc comments lines here
@@ -57,6 +107,11 @@ def main():
                     elif line.strip() == "":
                         pass
                     elif line.startswith("pub fn"):
+                        # Skip ignored functions before parsing, since their signatures might not
+                        # be parseable (e.g. generics)
+                        if re.match(r"pub +fn +(\w+)", line).group(1) in ignored_functions:
+                            comments = []
+                            continue
                         for comment in comments:
                             dest.write(comment)
                         comments = []
@@ -70,12 +125,10 @@ def main():
                         func_name_args = signature.split("->")[0]
 
                         name, argstr = re.match(r"pub +fn +(\w+)\((.*)\)", func_name_args, re.DOTALL).groups()
-                        if name in ignored_functions:
-                            continue
 
                         no_type_args = []
                         # s:&str, => s
-                        for arg in argstr.split(","):
+                        for arg in split_args(argstr):
                             var_name = re.sub(r":.*", "", arg).strip()
                             if '&Hash' in arg:
                                 no_type_args.append(var_name + ".as_ref()")
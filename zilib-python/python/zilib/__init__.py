@@ -22,10 +22,58 @@ def _initialize_resources():
     zilib.initialize_data("CantoneseCharListWithJyutping", str(package_path.joinpath('lists', 'charlist.json')))
     zilib.initialize_data("RadicalLabelToChars", str(package_path.joinpath('lists', 'CJKRadicals.txt')))
     zilib.initialize_data("EnglishVariants", str(package_path.joinpath('lists', 'english_variants.json')))
+    zilib.initialize_data("WordshkCharset", str(package_path.joinpath('lists', 'wordshk_charset.json')))
+    zilib.initialize_data("WordshkVariantMap", str(package_path.joinpath('lists', 'wordshk_variantmap.json')))
+    zilib.initialize_data("WordshkAutoconvert", str(package_path.joinpath('lists', 'wordshk_autoconvert.json')))
     # Unihan is not included in the package. So we don't initialize it here.
 
 _initialize_resources()
 
+def tokenize_jyutping(s, allow_exceptions=False):
+    """Splits a Jyutping string into syllables. Returns None if the string is
+    not valid Jyutping. See the rust function for details."""
+    return zilib.tokenize_jyutping(s, allow_exceptions)
+
+def canonicalize_jyutping(s, allow_exceptions=False):
+    """Canonicalizes the formatting of a Jyutping string. Returns None if the
+    string is not valid Jyutping. See the rust function for details."""
+    return zilib.canonicalize_jyutping(s, allow_exceptions)
+
+def jyutping_to_ipa(s, tone_notation="chao"):
+    """Converts the Jyutping in a string to IPA. tone_notation is "chao" for
+    Chao tone letters or "superscript" for superscript tone numbers. See the
+    rust function for details."""
+    return zilib.jyutping_to_ipa(s, tone_notation)
+
+def chars_with_pronunciation(query, mode="exact"):
+    """Gets the characters that are read as the syllable, as (character,
+    reading, count). mode is "exact", "toneless" or "initials". See the rust
+    function for details."""
+    return zilib.chars_with_pronunciation(query, mode)
+
+def words_with_pronunciation(query, mode="exact"):
+    """Gets the words that are read as the syllables, as (word, pronunciation,
+    count). mode is "exact", "toneless" or "initials". See the rust function
+    for details."""
+    return zilib.words_with_pronunciation(query, mode)
+
+def fuzzy_search(query, rules=None):
+    """Searches the word list for words whose pronunciation matches the
+    Jyutping query, tolerating lazy sounds (懶音) such as n/l. rules is a list
+    of merge rule names and defaults to all of them. See the rust function for
+    details."""
+    return zilib.fuzzy_search(query, rules)
+
+def generate_variants(s, reverse_map=None):
+    """Generates spellings of the string where characters are swapped for
+    their variants, e.g. to be used as search aliases. Unlike the rust
+    function, reverse_map defaults to wordshk_reverse_autoconvert(), since the
+    full variant map has too many rare variants for search aliases. See the
+    rust function for details."""
+    if reverse_map is None:
+        reverse_map = zilib.wordshk_reverse_autoconvert()
+    return zilib.generate_variants(s, reverse_map)
+
 def wordshk_charset():
     """Returns the words.hk character set. Used by words.hk as a reference to
     what words.hk considers as "canonical" character forms."""
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use zilib::cantonese;
 use zilib::common;
@@ -6,6 +6,13 @@ use zilib::english;
 use zilib::ruby_match;
 use zilib::segmentation;
 use zilib::data;
+use zilib::discovery;
+use zilib::variants;
+use zilib::romanization;
+use zilib::reverse_lookup;
+use zilib::lazy_sounds;
+use zilib::normalization;
+use zilib::user_dictionary;
 
 // Python (PyO3) bindings for functions in zilib
 
@@ -17,57 +24,82 @@ use pyo3::prelude::*;
 pub fn get_ping3jam1_from_charlist(chars:Vec<char>) -> Vec<Vec<String>> {
     cantonese::get_ping3jam1_from_charlist(chars)
 }
-/// Gets the pronunciation of a Cantonese string on a best effort basis. Each returned result
-/// corresponds to a character in the input string. IF there are multiple pronunciations for a
-/// character, multiple results are returned. If a character is not found in the dictionary, an
-/// empty list is returned.
+/// Gets the pronunciation of a Cantonese string on a best effort basis, as syllables separated by
+/// spaces. The string is segmented into words of wordlist, and each word gets its most common
+/// pronunciation. Characters without a known pronunciation (e.g. punctuation) give empty strings,
+/// so they only show up as extra spaces. Use get_ping3jam1_spans to align the pronunciation with
+/// the string.
 #[pyfunction]
 pub fn get_ping3jam1(s: &str) -> String {
     cantonese::get_ping3jam1(s)
 }
+/// Same as get_ping3jam1, but returns the pronunciation of each character with a confidence score
+/// (0-1), as (character, pronunciation, confidence). The confidence of a single character is the
+/// probability of its reading given its neighbours (see contextual_candidates), and the confidence
+/// of the characters of a word is the weight of the word's first pronunciation in wordlist. If the
+/// pronunciation of a word doesn't have one syllable per character (e.g. "!bas4" for 巴士), the
+/// whole pronunciation is given to the first character. Characters without a known pronunciation
+/// have an empty pronunciation and a confidence of 0.
+#[pyfunction]
+pub fn get_ping3jam1_with_confidence(s: &str) -> Vec<(char, String, f64)> {
+    cantonese::get_ping3jam1_with_confidence(s)
+}
+/// Same as get_ping3jam1, but syllables that are pronounced with a changed tone (變調) are annotated
+/// with the base tone, as "base*changed" (e.g. "mui6*2" for 妹 pronounced as mui2). See
+/// changed_tone_base for how changed tones are detected.
+#[pyfunction]
+pub fn get_ping3jam1_with_changed_tones(s: &str) -> String {
+    cantonese::get_ping3jam1_with_changed_tones(s)
+}
+/// Gets the n most likely pronunciations of a Cantonese string, from the most likely to the least
+/// likely. The string is segmented and each part of it (a word or a character) gets its candidate
+/// readings with their probabilities in the same way as in get_ping3jam1, so the first result is
+/// the same as get_ping3jam1 (without the extra spaces of characters without a pronunciation). The
+/// likelihood of a pronunciation is the product of the probabilities of the readings of its parts.
+/// Fewer than n results are returned if there aren't enough combinations.
+#[pyfunction]
+pub fn get_n_ping3jam1(s: &str, n: usize) -> Vec<String> {
+    cantonese::get_n_ping3jam1(s, n)
+}
 /// Regex string for validating formatting of Jyutping. Does not try to determine whether the
 /// pronunciation is valid.
 #[pyfunction]
 pub fn jyutping_validator_string() -> String {
     cantonese::jyutping_validator_string()
 }
-/// Regex for validating formatting of Jyutping. Does not try to determine whether the
-/// pronunciation is valid.
 /// Validates the formatting of a Jyutping string. Does not try to determine whether the
 /// pronunciation is valid.
 #[pyfunction]
 pub fn is_jyutping_valid(jyutping: &str) -> bool {
     cantonese::is_jyutping_valid(jyutping)
 }
-/// Ruby match. Returns a zipped (token, pronunciation) list of the structure of the match.
+/// Validates a Jyutping string strictly, i.e. the syllables must be actually used in Cantonese
+/// rather than just being well formatted. Returns a list of (syllable, reason) for each rejected
+/// syllable, which is empty if the string is valid.
 #[pyfunction]
-pub fn ruby_match_zipped(txt: &str, pronunciation: &str) -> Vec<(String, String)> {
-    ruby_match::ruby_match_zipped(txt, pronunciation)
+pub fn jyutping_rejections(jyutping: &str) -> Vec<(String, String)> {
+    cantonese::jyutping_rejections(jyutping)
 }
-/// Ruby match. Returns a plain text representation. Useful for unit testing (since the results are easier to understand)
+/// Validates a Jyutping string strictly. See jyutping_rejections for the reasons a string is
+/// rejected.
 #[pyfunction]
-pub fn ruby_match_plain(txt: &str, pronunciation: &str) -> String {
-    ruby_match::ruby_match_plain(txt, pronunciation)
+pub fn is_jyutping_valid_strict(jyutping: &str) -> bool {
+    cantonese::is_jyutping_valid_strict(jyutping)
 }
-/// Returns two lists as a pair: The first list contains indices of unmatched odd characters. The
-/// second list contains the indices of the segments (the segment's start index).
-#[pyfunction]
-pub fn segment_with_dictionary(phrase: &str, dictionary: Option<HashSet<String>>) -> (Vec<usize>, Vec<usize>) {
-    segmentation::segment_with_dictionary(phrase, dictionary.as_ref())
-}
-/// Returns a user-friendly segmentation result for text-based programs. If dictionary is None, we
-/// will load an out-of-date Cantonese dictionary from words.hk
+/// Splits a Jyutping string into syllables, also splitting syllables that are written together
+/// (e.g. "gaa1ze1" => ["gaa1", "ze1"]). If allow_exceptions is true, exception tokens starting with
+/// '!' (e.g. "!T") are kept as separate tokens. Returns None if any part of the string is not
+/// valid Jyutping.
 #[pyfunction]
-pub fn end_user_friendly_segment(s: &str, dictionary: Option<HashSet<String>>) -> (Vec<char>, Vec<char>, Vec<String>) {
-    segmentation::end_user_friendly_segment(s, dictionary.as_ref())
+pub fn tokenize_jyutping(s: &str, allow_exceptions: bool) -> Option<Vec<String>> {
+    cantonese::tokenize_jyutping(s, allow_exceptions)
 }
+/// Canonicalizes the formatting of a Jyutping string, so that syllables are separated by exactly
+/// one space (e.g. " gaa1ze1 " => "gaa1 ze1"). See tokenize_jyutping for allow_exceptions. Returns
+/// None if the string is not valid Jyutping.
 #[pyfunction]
-pub fn usa_english(word : &str) -> String {
-    english::usa_english(word)
-}
-#[pyfunction]
-pub fn american_english_stem(w: &str) -> String {
-    english::american_english_stem(w)
+pub fn canonicalize_jyutping(s: &str, allow_exceptions: bool) -> Option<String> {
+    cantonese::canonicalize_jyutping(s, allow_exceptions)
 }
 /// Returns the number of characters that are in the "Letter" category in unicode"""
 #[pyfunction]
@@ -115,6 +147,161 @@ pub fn looks_like_a_sentence(s: &str) -> bool {
 pub fn guess_language(s: &str) -> String {
     common::guess_language(s)
 }
+#[pyfunction]
+pub fn usa_english(word : &str) -> String {
+    english::usa_english(word)
+}
+#[pyfunction]
+pub fn american_english_stem(w: &str) -> String {
+    english::american_english_stem(w)
+}
+/// Reads a string of digits one by one, e.g. "2024" => "二零二四". Returns None if the string has
+/// anything other than ASCII digits.
+#[pyfunction]
+pub fn verbalize_digits(s: &str) -> Option<String> {
+    normalization::verbalize_digits(s)
+}
+/// Reads a non-negative integer, e.g. 1500 => "一千五百" and 10010 => "一萬零一十". A leading 2 is
+/// read as 兩 before 百, 千, 萬 and 億 (e.g. "兩千"). Numbers of 10^16 and above are read digit by
+/// digit.
+#[pyfunction]
+pub fn verbalize_integer(n: u64) -> String {
+    normalization::verbalize_integer(n)
+}
+/// Reads a number, which can have a minus sign, thousands separators and decimals, e.g.
+/// "-1,234.5" => "負一千二百三十四點五". Returns None if the string is not a number.
+#[pyfunction]
+pub fn verbalize_number(s: &str) -> Option<String> {
+    normalization::verbalize_number(s)
+}
+/// Splits the string into pieces that add up to the string, as (original text, normalized text).
+/// Pieces that are not changed by the normalization have the same original and normalized text.
+/// Expressions that are written together with other Latin letters or digits are left as they are,
+/// so e.g. "3D" and "MP3" are not changed, and so are expressions inside words of wordlist, so e.g.
+/// "7-11" and "133" are read as the words.
+#[pyfunction]
+pub fn normalize_text_pieces(s: &str) -> Vec<(String, String)> {
+    normalization::normalize_text_pieces(s)
+}
+/// Normalizes the string for pronunciation lookup, by verbalizing numbers, dates, times,
+/// percentages, amounts of money and common abbreviations into Cantonese words, e.g. "HK$1,500"
+/// => "一千五百蚊" and "10:30pm" => "下晝十點半". See normalize_text_pieces for what is left as
+/// it is.
+#[pyfunction]
+pub fn normalize_text(s: &str) -> String {
+    normalization::normalize_text(s)
+}
+/// Converts a Jyutping string to Yale, with diacritics or tone numbers. See syllable_to_yale.
+/// Returns None if the string is not valid Jyutping, or has a syllable with no Yale spelling.
+#[pyfunction]
+pub fn jyutping_to_yale(jyutping: &str, diacritics: bool) -> Option<String> {
+    romanization::jyutping_to_yale(jyutping, diacritics)
+}
+/// Converts a Yale string (syllables separated by spaces) to Jyutping. Both diacritics and tone
+/// numbers are accepted. Returns None if the string is not valid Yale.
+#[pyfunction]
+pub fn yale_to_jyutping(yale: &str) -> Option<String> {
+    romanization::yale_to_jyutping(yale)
+}
+/// Converts a Jyutping string to Cantonese Pinyin. See syllable_to_cantonese_pinyin. Returns None
+/// if the string is not valid Jyutping.
+#[pyfunction]
+pub fn jyutping_to_cantonese_pinyin(jyutping: &str) -> Option<String> {
+    romanization::jyutping_to_cantonese_pinyin(jyutping)
+}
+/// Converts a Cantonese Pinyin string (syllables separated by spaces) to Jyutping. Returns None if
+/// any syllable can't be converted. See cantonese_pinyin_to_syllable.
+#[pyfunction]
+pub fn cantonese_pinyin_to_jyutping(s: &str) -> Option<String> {
+    romanization::cantonese_pinyin_to_jyutping(s)
+}
+/// Converts a Jyutping string to Sidney Lau romanization. See syllable_to_sidney_lau. Returns None
+/// if the string is not valid Jyutping.
+#[pyfunction]
+pub fn jyutping_to_sidney_lau(jyutping: &str) -> Option<String> {
+    romanization::jyutping_to_sidney_lau(jyutping)
+}
+/// Converts a Sidney Lau string (syllables separated by spaces) to Jyutping. Returns None if any
+/// syllable can't be converted.
+#[pyfunction]
+pub fn sidney_lau_to_jyutping(s: &str) -> Option<String> {
+    romanization::sidney_lau_to_jyutping(s)
+}
+/// Ruby match. Returns a zipped (token, pronunciation) list of the structure of the match.
+#[pyfunction]
+pub fn ruby_match_zipped(txt: &str, pronunciation: &str) -> Vec<(String, String)> {
+    ruby_match::ruby_match_zipped(txt, pronunciation)
+}
+/// Ruby match. Returns a plain text representation. Useful for unit testing (since the results are easier to understand)
+#[pyfunction]
+pub fn ruby_match_plain(txt: &str, pronunciation: &str) -> String {
+    ruby_match::ruby_match_plain(txt, pronunciation)
+}
+/// Returns two lists as a pair: The first list contains indices of unmatched odd characters. The
+/// second list contains the indices of the segments (the segment's start index). Latin words,
+/// numbers, URLs, emoji, punctuation and whitespace that are not part of dictionary words are
+/// segments of their own and are not odd (see segment_text). If dictionary is None, the default
+/// segmenter (see default_segmenter) is used, and otherwise the words of the dictionary are scored
+/// with DictionaryModel. Use a Segmenter to segment many strings with the same dictionary.
+#[pyfunction]
+pub fn segment_with_dictionary(phrase: &str, dictionary: Option<HashSet<String>>) -> (Vec<usize>, Vec<usize>) {
+    segmentation::segment_with_dictionary(phrase, dictionary.as_ref())
+}
+/// Gets the n best segmentations of the phrase, from the best to the worst, as (cost, indices of
+/// unmatched odd characters, indices of the segments). The first one is the same as
+/// segment_with_dictionary. The cost is the total cost of the segments (see SegmentationModel), so
+/// lower is better. Fewer than n segmentations are returned if there aren't enough of them.
+#[pyfunction]
+pub fn segment_n_best(phrase: &str, n: usize, dictionary: Option<HashSet<String>>) -> Vec<(f32, Vec<usize>, Vec<usize>)> {
+    segmentation::segment_n_best(phrase, n, dictionary.as_ref())
+}
+/// Gets the word lattice of the phrase, i.e. every word of the dictionary in the phrase, as (start
+/// index, end index, cost), sorted by the start and then the end. Indices are in characters. See
+/// segment_with_dictionary for the dictionary.
+#[pyfunction]
+pub fn word_lattice(phrase: &str, dictionary: Option<HashSet<String>>) -> Vec<(usize, usize, f32)> {
+    segmentation::word_lattice(phrase, dictionary.as_ref())
+}
+/// Returns a user-friendly segmentation result for text-based programs, as (characters in runs of
+/// two or more odd characters, odd characters, segments). Latin words, numbers, URLs, emoji,
+/// punctuation and whitespace are segments of their own and are not odd, so e.g. "boss" is one
+/// segment. If dictionary is None, the default segmenter (see default_segmenter) is used. Use
+/// segment_text for the offsets and kinds of the segments.
+#[pyfunction]
+pub fn end_user_friendly_segment(s: &str, dictionary: Option<HashSet<String>>) -> (Vec<char>, Vec<char>, Vec<String>) {
+    segmentation::end_user_friendly_segment(s, dictionary.as_ref())
+}
+/// Converts the characters in the string to their words.hk canonical forms, using only the "safe"
+/// autoconvert map. This is what words.hk applies automatically to user input.
+#[pyfunction]
+pub fn hk_variant_autoconvert(s: &str) -> String {
+    variants::hk_variant_autoconvert(s)
+}
+/// Converts the characters in the string to their words.hk canonical forms, using the full variant
+/// map in addition to the autoconvert map. Characters that are already in the canonical character
+/// set are never converted, even if the variant map has an entry for them, since such conversions
+/// are lossy (e.g. 著 => 着).
+#[pyfunction]
+pub fn hk_variant(s: &str) -> String {
+    variants::hk_variant(s)
+}
+/// Returns the CJK characters in the string that are not in the words.hk canonical character set,
+/// as a list of (char offset, character, suggested canonical replacement). The suggestion comes
+/// from the variant maps and is None if we don't know of a canonical form. Non-CJK characters are
+/// not checked.
+#[pyfunction]
+pub fn conformant(s: &str) -> Vec<(usize, char, Option<char>)> {
+    variants::conformant(s)
+}
+/// Generates spellings of the string where characters are swapped for their variants, e.g. to be
+/// used as search aliases. reverse_map maps a character to its variants, and defaults to
+/// wordshk_reverse_variantmap (pass wordshk_reverse_autoconvert for common variants only). Runs
+/// of identical characters are always spelt the same way (so 一一 gives ①① but not 一①). The
+/// original string is not included, and at most GENERATE_VARIANTS_MAX results are returned.
+#[pyfunction]
+pub fn generate_variants(s: &str, reverse_map: Option<HashMap<char, String>>) -> Vec<String> {
+    variants::generate_variants(s, reverse_map.as_ref())
+}
 /* END_OF_GENERATED_FUNCTION_WRAPPERS */
 
 #[pyfunction]
@@ -141,6 +328,289 @@ pub fn binary_search_file(
 
 }
 
+/// Converts the Jyutping in a string to IPA. tone_notation is "chao" for Chao tone letters or
+/// "superscript" for superscript tone numbers. Tokens that are not Jyutping are kept unchanged.
+#[pyfunction]
+pub fn jyutping_to_ipa(s: &str, tone_notation: &str) -> std::io::Result<String> {
+    let tones = match tone_notation {
+        "chao" => romanization::ToneNotation::ChaoLetters,
+        "superscript" => romanization::ToneNotation::SuperscriptNumbers,
+        _ => { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid tone notation string")); }
+    };
+    Ok(romanization::jyutping_to_ipa(s, tones))
+}
+
+/// Gets the characters that are read as the syllable, as (character, reading, charlist count),
+/// from the most common to the least common. mode is "exact", "toneless" or "initials".
+#[pyfunction]
+pub fn chars_with_pronunciation(query: &str, mode: &str) -> std::io::Result<Vec<(char, String, u64)>> {
+    Ok(reverse_lookup::chars_with_pronunciation(query, query_mode(mode)?))
+}
+
+/// Gets the words in wordlist that are read as the syllables, as (word, pronunciation, count),
+/// from the most common to the least common. mode is "exact", "toneless" or "initials".
+#[pyfunction]
+pub fn words_with_pronunciation(query: &str, mode: &str) -> std::io::Result<Vec<(String, String, u64)>> {
+    Ok(reverse_lookup::words_with_pronunciation(query, query_mode(mode)?))
+}
+
+/// Searches wordlist for words whose pronunciation matches the Jyutping query when the sounds
+/// merged by the rules are considered the same. rules are the names of the merge rules (e.g.
+/// "n/l", "ng-", "tone"), and default to all of them. Returns (word, pronunciation, cost) sorted by
+/// the cost.
+#[pyfunction]
+pub fn fuzzy_search(query: &str, rules: Option<Vec<String>>) -> std::io::Result<Vec<(String, String, u32)>> {
+    let rules = match rules {
+        Some(rules) => merge_rules(&rules)?,
+        None => lazy_sounds::ALL_MERGE_RULES.to_vec(),
+    };
+    Ok(lazy_sounds::fuzzy_search(query, &rules))
+}
+
+/// Gets the careful speech (i.e. not lazy sound) forms that a Jyutping string could stand for, with
+/// the names of the rules that map them to the string. The candidates with the fewest changes come
+/// first.
+#[pyfunction]
+pub fn careful_speech_candidates(jyutping: &str) -> Vec<(String, Vec<String>)> {
+    lazy_sounds::careful_speech_candidates(jyutping).into_iter()
+        .map(|(careful, rules)| (careful, rules.iter().map(|r| r.to_string()).collect()))
+        .collect()
+}
+
+/// Scans the word list for pronunciations that look like lazy sounds. Returns (word,
+/// pronunciation, careful speech pronunciation, names of the rules) sorted by the word.
+#[pyfunction]
+pub fn find_lazy_sound_entries() -> Vec<(String, String, String, Vec<String>)> {
+    lazy_sounds::find_lazy_sound_entries().into_iter()
+        .map(|(word, p, careful, rules)| (word, p, careful, rules.iter().map(|r| r.to_string()).collect()))
+        .collect()
+}
+
+/// Same as get_ping3jam1, but returns the reading of every span of the string, as (text, start,
+/// end, syllables, source). start and end are character indices into the string, and source is
+/// "wordlist", "charlist", "unknown", "normalized" or "user". The spans cover the whole string.
+#[pyfunction]
+pub fn get_ping3jam1_spans(s: &str) -> Vec<(String, usize, usize, Vec<String>, &'static str)> {
+    cantonese::get_ping3jam1_spans(s).into_iter()
+        .map(|span| (span.text, span.start, span.end, span.readings, span.source.name()))
+        .collect()
+}
+
+/// Segments the phrase into typed tokens, as (token, kind), where kind is "word", "odd", "latin",
+/// "digits", "url", "emoji", "punctuation" or "whitespace". See the rust function for details.
+#[pyfunction]
+#[pyo3(signature = (phrase, dictionary=None))]
+pub fn segment_tokens(phrase: &str, dictionary: Option<HashSet<String>>) -> Vec<(String, &'static str)> {
+    segmentation::segment_tokens(phrase, dictionary.as_ref()).into_iter()
+        .map(|(token, kind)| (token, kind.name()))
+        .collect()
+}
+
+/// Segments the string into typed tokens with their offsets. See the rust function for details.
+#[pyfunction]
+#[pyo3(signature = (s, dictionary=None))]
+pub fn segment_text(s: &str, dictionary: Option<HashSet<String>>) -> PySegmentation {
+    PySegmentation { segmentation: segmentation::segment_text(s, dictionary.as_ref()) }
+}
+
+/// Finds candidate new words in the lines of the corpus, as (text, count, cohesion, left entropy,
+/// right entropy, score) from the best to the worst. If dictionary is None, the words of wordlist
+/// are used. See the rust function for details.
+#[pyfunction]
+#[pyo3(signature = (lines, dictionary=None, min_len=2, max_len=4, min_count=3, min_cohesion=1.0, min_entropy=0.5))]
+pub fn discover_words(lines: Vec<String>, dictionary: Option<HashSet<String>>, min_len: usize, max_len: usize, min_count: u64, min_cohesion: f64, min_entropy: f64) -> Vec<(String, u64, f64, f64, f64, f64)> {
+    let options = discovery::DiscoveryOptions { min_len, max_len, min_count, min_cohesion, min_entropy };
+    let mut discoverer = match dictionary {
+        Some(dictionary) => discovery::WordDiscoverer::with_dictionary(dictionary.iter().map(|w| w.as_str()), options),
+        None => discovery::WordDiscoverer::new(options),
+    };
+    for line in &lines {
+        discoverer.add_line(line);
+    }
+    discoverer.candidates().into_iter()
+        .map(|c| (c.text, c.count, c.cohesion, c.left_entropy, c.right_entropy, c.score))
+        .collect()
+}
+
+/// A map of (words.hk canonical character) => (variant characters), i.e. the reverse of the
+/// autoconvert map, which only has common variants. Can be passed to generate_variants.
+#[pyfunction]
+pub fn wordshk_reverse_autoconvert() -> HashMap<char, String> {
+    variants::wordshk_reverse_autoconvert().clone()
+}
+
+type TokenTuple = (String, usize, usize, usize, usize, &'static str);
+
+fn token_tuple(token: &segmentation::Token) -> TokenTuple {
+    (token.text.clone(), token.start, token.end, token.byte_start, token.byte_end, token.kind.name())
+}
+
+/// The result of segment_text. Tokens and runs are tuples of (text, start, end, byte_start,
+/// byte_end, kind), where start and end are character indices into the string.
+#[pyclass(name = "Segmentation")]
+pub struct PySegmentation {
+    segmentation: segmentation::Segmentation,
+}
+
+#[pymethods]
+impl PySegmentation {
+    /// The tokens, which cover the whole string in order.
+    #[getter]
+    fn tokens(&self) -> Vec<TokenTuple> {
+        self.segmentation.tokens.iter().map(token_tuple).collect()
+    }
+
+    /// The total cost of the segmentation.
+    #[getter]
+    fn cost(&self) -> f32 {
+        self.segmentation.cost
+    }
+
+    /// The texts of the tokens.
+    fn texts(&self) -> Vec<&str> {
+        self.segmentation.texts()
+    }
+
+    /// The runs of consecutive odd characters.
+    fn odd_runs(&self) -> Vec<TokenTuple> {
+        self.segmentation.odd_runs().iter().map(token_tuple).collect()
+    }
+
+    /// The runs of two or more consecutive odd characters.
+    fn bad_runs(&self) -> Vec<TokenTuple> {
+        self.segmentation.bad_runs().iter().map(token_tuple).collect()
+    }
+}
+
+/// A compiled segmentation dictionary for segmenting many strings. By default it has the words of
+/// the word list, and words are added to them. If default_words is False, only the given words are
+/// used, scored the same way as segment_with_dictionary.
+#[pyclass(name = "Segmenter")]
+pub struct PySegmenter {
+    segmenter: segmentation::Segmenter,
+}
+
+#[pymethods]
+impl PySegmenter {
+    #[new]
+    #[pyo3(signature = (words=None, default_words=true))]
+    fn new(words: Option<Vec<String>>, default_words: bool) -> Self {
+        let words = words.unwrap_or_default();
+        let segmenter = if default_words {
+            segmentation::Segmenter::new().with_words(words.iter().map(|w| w.as_str()))
+        } else {
+            segmentation::Segmenter::from_dictionary(words.iter().map(|w| w.as_str()))
+        };
+        PySegmenter { segmenter }
+    }
+
+    /// Adds a word to the dictionary.
+    fn add_word(&mut self, word: &str) {
+        self.segmenter.add_word(word)
+    }
+
+    /// Same as add_word, but the word is weight times as likely. See the rust function for details.
+    fn add_word_with_weight(&mut self, word: &str, weight: f32) {
+        self.segmenter.add_word_with_weight(word, weight)
+    }
+
+    /// Same as segment_with_dictionary, but with the dictionary of the segmenter.
+    fn segment(&self, phrase: &str) -> (Vec<usize>, Vec<usize>) {
+        self.segmenter.segment(phrase)
+    }
+
+    /// Same as end_user_friendly_segment, but with the dictionary of the segmenter.
+    fn end_user_friendly_segment(&self, s: &str) -> (Vec<char>, Vec<char>, Vec<String>) {
+        self.segmenter.end_user_friendly_segment(s)
+    }
+
+    /// Same as segment_text, but with the dictionary of the segmenter.
+    fn segment_text(&self, s: &str) -> PySegmentation {
+        PySegmentation { segmentation: self.segmenter.segment_text(s) }
+    }
+
+    /// Same as segment_tokens, but with the dictionary of the segmenter.
+    fn segment_tokens(&self, phrase: &str) -> Vec<(String, &'static str)> {
+        self.segmenter.segment_tokens(phrase).into_iter()
+            .map(|(token, kind)| (token, kind.name()))
+            .collect()
+    }
+
+    /// Same as segment_n_best, but with the dictionary of the segmenter.
+    fn n_best(&self, phrase: &str, n: usize) -> Vec<(f32, Vec<usize>, Vec<usize>)> {
+        self.segmenter.n_best(phrase, n)
+    }
+
+    /// Same as word_lattice, but with the dictionary of the segmenter.
+    fn word_lattice(&self, phrase: &str) -> Vec<(usize, usize, f32)> {
+        self.segmenter.word_lattice(phrase)
+    }
+}
+
+/// A dictionary made of layers on top of the word list, e.g. project words on top of the word list.
+/// Layers are added from TSV files (see the rust function UserDictionary::from_tsv) and can add
+/// words with weights and forced pronunciations, or suppress words of the layers below.
+#[pyclass(name = "LayeredDictionary")]
+pub struct PyLayeredDictionary {
+    dictionary: user_dictionary::LayeredDictionary,
+}
+
+#[pymethods]
+impl PyLayeredDictionary {
+    #[new]
+    fn new() -> Self {
+        PyLayeredDictionary { dictionary: user_dictionary::LayeredDictionary::new() }
+    }
+
+    /// Adds a layer in TSV format on top of the others.
+    fn add_tsv(&mut self, tsv: &str) -> std::io::Result<()> {
+        let layer = user_dictionary::UserDictionary::from_tsv(tsv).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        self.dictionary.push_layer(layer);
+        Ok(())
+    }
+
+    /// Adds a layer from a TSV file on top of the others.
+    fn load_tsv(&mut self, path: &str) -> std::io::Result<()> {
+        self.dictionary.push_layer(user_dictionary::UserDictionary::load_tsv(path)?);
+        Ok(())
+    }
+
+    /// The forced pronunciation of the word, if a layer has one for it.
+    fn pronunciation(&self, word: &str) -> Option<String> {
+        self.dictionary.pronunciation(word).map(|p| p.to_string())
+    }
+
+    /// Same as end_user_friendly_segment, but with the words of the dictionary.
+    fn end_user_friendly_segment(&self, s: &str) -> (Vec<char>, Vec<char>, Vec<String>) {
+        self.dictionary.segmenter().end_user_friendly_segment(s)
+    }
+
+    /// Same as segment_text, but with the words of the dictionary.
+    fn segment_text(&self, s: &str) -> PySegmentation {
+        PySegmentation { segmentation: self.dictionary.segmenter().segment_text(s) }
+    }
+
+    /// Same as get_ping3jam1, but with the words and pronunciations of the dictionary.
+    fn get_ping3jam1(&self, s: &str) -> String {
+        self.dictionary.get_ping3jam1(s)
+    }
+}
+
+fn merge_rules(rules: &[String]) -> std::io::Result<Vec<lazy_sounds::MergeRule>> {
+    rules.iter()
+        .map(|r| r.parse().map_err(|e: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))
+        .collect()
+}
+
+fn query_mode(mode: &str) -> std::io::Result<reverse_lookup::QueryMode> {
+    match mode {
+        "exact" => Ok(reverse_lookup::QueryMode::Exact),
+        "toneless" => Ok(reverse_lookup::QueryMode::Toneless),
+        "initials" => Ok(reverse_lookup::QueryMode::Initials),
+        _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid query mode string")),
+    }
+}
+
 #[pyfunction]
 pub fn initialize_data(kind: &str, path: &str) -> std::io::Result<()> {
     let kind = match kind {
@@ -149,6 +619,9 @@ pub fn initialize_data(kind: &str, path: &str) -> std::io::Result<()> {
         "RadicalLabelToChars" => data::DataKind::RadicalLabelToChars,
         "UnihanData" => data::DataKind::UnihanData,
         "EnglishVariants" => data::DataKind::EnglishVariants,
+        "WordshkCharset" => data::DataKind::WordshkCharset,
+        "WordshkVariantMap" => data::DataKind::WordshkVariantMap,
+        "WordshkAutoconvert" => data::DataKind::WordshkAutoconvert,
         _ => { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid data kind string")); }
     };
 
@@ -162,14 +635,15 @@ fn zilib_python(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     /* START_OF_GENERATED_ADD_FUNCTIONS */
     m.add_function(wrap_pyfunction!(get_ping3jam1_from_charlist, m)?)?;
     m.add_function(wrap_pyfunction!(get_ping3jam1, m)?)?;
+    m.add_function(wrap_pyfunction!(get_ping3jam1_with_confidence, m)?)?;
+    m.add_function(wrap_pyfunction!(get_ping3jam1_with_changed_tones, m)?)?;
+    m.add_function(wrap_pyfunction!(get_n_ping3jam1, m)?)?;
     m.add_function(wrap_pyfunction!(jyutping_validator_string, m)?)?;
     m.add_function(wrap_pyfunction!(is_jyutping_valid, m)?)?;
-    m.add_function(wrap_pyfunction!(ruby_match_zipped, m)?)?;
-    m.add_function(wrap_pyfunction!(ruby_match_plain, m)?)?;
-    m.add_function(wrap_pyfunction!(segment_with_dictionary, m)?)?;
-    m.add_function(wrap_pyfunction!(end_user_friendly_segment, m)?)?;
-    m.add_function(wrap_pyfunction!(usa_english, m)?)?;
-    m.add_function(wrap_pyfunction!(american_english_stem, m)?)?;
+    m.add_function(wrap_pyfunction!(jyutping_rejections, m)?)?;
+    m.add_function(wrap_pyfunction!(is_jyutping_valid_strict, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize_jyutping, m)?)?;
+    m.add_function(wrap_pyfunction!(canonicalize_jyutping, m)?)?;
     m.add_function(wrap_pyfunction!(letter_count, m)?)?;
     m.add_function(wrap_pyfunction!(remove_unicode_other, m)?)?;
     m.add_function(wrap_pyfunction!(is_cjk_cp, m)?)?;
@@ -179,11 +653,48 @@ fn zilib_python(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(is_latin_c, m)?)?;
     m.add_function(wrap_pyfunction!(looks_like_a_sentence, m)?)?;
     m.add_function(wrap_pyfunction!(guess_language, m)?)?;
+    m.add_function(wrap_pyfunction!(usa_english, m)?)?;
+    m.add_function(wrap_pyfunction!(american_english_stem, m)?)?;
+    m.add_function(wrap_pyfunction!(verbalize_digits, m)?)?;
+    m.add_function(wrap_pyfunction!(verbalize_integer, m)?)?;
+    m.add_function(wrap_pyfunction!(verbalize_number, m)?)?;
+    m.add_function(wrap_pyfunction!(normalize_text_pieces, m)?)?;
+    m.add_function(wrap_pyfunction!(normalize_text, m)?)?;
+    m.add_function(wrap_pyfunction!(jyutping_to_yale, m)?)?;
+    m.add_function(wrap_pyfunction!(yale_to_jyutping, m)?)?;
+    m.add_function(wrap_pyfunction!(jyutping_to_cantonese_pinyin, m)?)?;
+    m.add_function(wrap_pyfunction!(cantonese_pinyin_to_jyutping, m)?)?;
+    m.add_function(wrap_pyfunction!(jyutping_to_sidney_lau, m)?)?;
+    m.add_function(wrap_pyfunction!(sidney_lau_to_jyutping, m)?)?;
+    m.add_function(wrap_pyfunction!(ruby_match_zipped, m)?)?;
+    m.add_function(wrap_pyfunction!(ruby_match_plain, m)?)?;
+    m.add_function(wrap_pyfunction!(segment_with_dictionary, m)?)?;
+    m.add_function(wrap_pyfunction!(segment_n_best, m)?)?;
+    m.add_function(wrap_pyfunction!(word_lattice, m)?)?;
+    m.add_function(wrap_pyfunction!(end_user_friendly_segment, m)?)?;
+    m.add_function(wrap_pyfunction!(hk_variant_autoconvert, m)?)?;
+    m.add_function(wrap_pyfunction!(hk_variant, m)?)?;
+    m.add_function(wrap_pyfunction!(conformant, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_variants, m)?)?;
     /* END_OF_GENERATED_ADD_FUNCTIONS */
 
     m.add_function(wrap_pyfunction!(ruby_match_max, m)?)?;
     m.add_function(wrap_pyfunction!(binary_search_file, m)?)?;
+    m.add_function(wrap_pyfunction!(jyutping_to_ipa, m)?)?;
+    m.add_function(wrap_pyfunction!(chars_with_pronunciation, m)?)?;
+    m.add_function(wrap_pyfunction!(words_with_pronunciation, m)?)?;
+    m.add_function(wrap_pyfunction!(fuzzy_search, m)?)?;
+    m.add_function(wrap_pyfunction!(careful_speech_candidates, m)?)?;
+    m.add_function(wrap_pyfunction!(find_lazy_sound_entries, m)?)?;
+    m.add_function(wrap_pyfunction!(get_ping3jam1_spans, m)?)?;
+    m.add_function(wrap_pyfunction!(segment_tokens, m)?)?;
+    m.add_function(wrap_pyfunction!(segment_text, m)?)?;
+    m.add_function(wrap_pyfunction!(discover_words, m)?)?;
+    m.add_function(wrap_pyfunction!(wordshk_reverse_autoconvert, m)?)?;
     m.add_function(wrap_pyfunction!(initialize_data, m)?)?;
+    m.add_class::<PySegmenter>()?;
+    m.add_class::<PySegmentation>()?;
+    m.add_class::<PyLayeredDictionary>()?;
 
     Ok(())
 }
@@ -2,6 +2,7 @@
 
 import zilib
 import unittest
+import math
 
 class LocalTests(unittest.TestCase):
     def test_degenerate(self):
@@ -71,6 +72,79 @@ class LocalTests(unittest.TestCase):
         p = "puk1 bok3 si6 puk1 bok3 si6 puk1 bok3 si6 puk1 bok3 si6" # Wrong pronunciation for testing
         self.assertEqual(rm(t, p), "劉puk1 博bok3 士si6 劉puk1 博bok3 士si6 劉puk1 博bok3 士si6 劉puk1 博bok3 士si6")
 
+    def test_ruby_match_changed_tone(self):
+        # 銅 is tung4, so tung2 (a changed tone) is a better match than tung3
+        self.assertEqual(zilib.ruby_match_plain("老銅", "lou5 tung3 tung2"), "老lou5tung3 銅tung2")
+        self.assertEqual(zilib.ruby_match_plain("老銅", "lou5 tung2 tung3"), "老lou5 銅tung2tung3")
+
+    def test_ping3jam1_context(self):
+        # 長 on its own is coeng4, but zoeng2 next to 大
+        self.assertEqual(zilib.get_ping3jam1("長"), "coeng4")
+        self.assertEqual(zilib.get_ping3jam1("佢越大長得越高"), "keoi5 jyut6 daai6 zoeng2 dak1 jyut6 gou1")
+
+        result = zilib.get_ping3jam1_with_confidence("佢越大長得越高")
+        self.assertEqual([(c, p) for c, p, _ in result][3], ("長", "zoeng2"))
+        self.assertTrue(0.5 < result[3][2] < 1)
+        # Words and characters with only one reading are certain
+        self.assertEqual(zilib.get_ping3jam1_with_confidence("我哋"), [("我", "ngo5", 1.0), ("哋", "dei6", 1.0)])
+        self.assertEqual(zilib.get_ping3jam1_with_confidence("!"), [("!", "", 0.0)])
+
+    def test_ping3jam1_spans(self):
+        spans = zilib.get_ping3jam1_spans("我哋去巴士站, ok?")
+        self.assertEqual(spans[:3], [
+            ("我哋", 0, 2, ["ngo5", "dei6"], "wordlist"),
+            ("去", 2, 3, ["heoi3"], "wordlist"),
+            ("巴士站", 3, 6, ["baa1", "si2", "zaam6"], "wordlist"),
+        ])
+        self.assertEqual(spans[3], (",", 6, 7, [], "unknown"))
+        # The spans cover the whole string
+        self.assertEqual("".join(text for text, _, _, _, _ in spans), "我哋去巴士站, ok?")
+        for (_, _, end, _, _), (_, start, _, _, _) in zip(spans, spans[1:]):
+            self.assertEqual(end, start)
+        self.assertEqual(spans[-1][1:], (10, 11, [], "unknown"))
+
+    def test_normalization(self):
+        self.assertEqual(zilib.verbalize_integer(10010), "一萬零一十")
+        self.assertEqual(zilib.verbalize_integer(2000), "兩千")
+        self.assertEqual(zilib.verbalize_number("-1,234.5"), "負一千二百三十四點五")
+        self.assertEqual(zilib.verbalize_number("1,2"), None)
+        self.assertEqual(zilib.normalize_text("2024年3月15日"), "二零二四年三月十五日")
+        self.assertEqual(zilib.normalize_text("15/3/2024"), "二零二四年三月十五日")
+        self.assertEqual(zilib.normalize_text("HK$1,500"), "一千五百蚊")
+        self.assertEqual(zilib.normalize_text("$12.50"), "十二蚊五毫")
+        self.assertEqual(zilib.normalize_text("US$100"), "一百美金")
+        self.assertEqual(zilib.normalize_text("10:30pm"), "下晝十點半")
+        self.assertEqual(zilib.normalize_text("10:05"), "十點零五分")
+        self.assertEqual(zilib.normalize_text("加價50%"), "加價百分之五十")
+        self.assertEqual(zilib.normalize_text("2個2月"), "兩個二月")
+        # Numbers written together with Latin letters are left alone
+        self.assertEqual(zilib.normalize_text("3D打印 MP3"), "3D打印 MP3")
+        # Words of wordlist are read as they are
+        self.assertEqual(zilib.normalize_text_pieces("7-11"), [("7-11", "7-11")])
+        self.assertEqual(zilib.get_ping3jam1("133"), "jat1 saam1 saam1")
+        self.assertEqual(zilib.get_ping3jam1("去7-11買嘢"), "heoi3 cat1 sap6 jat1 maai5 je5")
+        self.assertEqual(zilib.normalize_text("２０２４年３月"), "二零二四年三月")
+        self.assertEqual(zilib.verbalize_integer(10001000), "一千萬一千")
+        self.assertEqual(zilib.verbalize_integer(10000100), "一千萬零一百")
+        self.assertEqual(zilib.verbalize_integer(100001000), "一億零一千")
+
+        self.assertEqual(zilib.get_ping3jam1("HK$1,500"), "jat1 cin1 ng5 baak3 man1")
+        self.assertEqual(zilib.get_ping3jam1("我要2個"), "ngo5 jiu3 loeng5 go3")
+        # get_n_ping3jam1 reads the string in the same way as get_ping3jam1
+        self.assertEqual(zilib.get_n_ping3jam1("件衫$1,500", 2), ["gin6 saam1 jat1 cin1 ng5 baak3 man1", "gin2 saam1 jat1 cin1 ng5 baak3 man1"])
+        self.assertEqual(zilib.get_ping3jam1_spans("件衫$1,500"), [
+            ("件", 0, 1, ["gin6"], "wordlist"),
+            ("衫", 1, 2, ["saam1"], "wordlist"),
+            ("$1,500", 2, 8, ["jat1", "cin1", "ng5", "baak3", "man1"], "normalized"),
+        ])
+
+    def test_changed_tones(self):
+        self.assertEqual(zilib.get_ping3jam1_with_changed_tones("妹妹"), "mui4 mui6*2")
+        self.assertEqual(zilib.get_ping3jam1_with_changed_tones("阿姨"), "aa3 ji4*1")
+        self.assertEqual(zilib.get_ping3jam1_with_changed_tones("食魚蛋"), "sik6 jyu4 daan6*2")
+        # Both hou2 and hou3 are readings of 好
+        self.assertEqual(zilib.get_ping3jam1_with_changed_tones("好人"), "hou2 jan4")
+
     def test_segmentation(self):
         self.assertEqual(zilib.end_user_friendly_segment("你真係咩事屈機呀唔知死未!")[2], ['你', '真係', '咩事', '屈機', '呀', '唔知死', '未', '!'])
         self.assertEqual(zilib.end_user_friendly_segment("你真係咩事屈機呀死未知數!")[2], ['你', '真係', '咩事', '屈機', '呀', '死', '未知數', '!'])
@@ -79,6 +153,246 @@ class LocalTests(unittest.TestCase):
         self.assertEqual(zilib.end_user_friendly_segment('中國人')[2], '中國 人'.split())
         self.assertEqual(zilib.end_user_friendly_segment('唔知道')[2], '唔知 道'.split())  # Maybe we should use some other heuristic for this to ensure the singled out word is more commonly used as single word or something using frequency lists
 
+    def test_segmenter(self):
+        segmenter = zilib.Segmenter()
+        for s in ["你真係咩事屈機呀唔知死未!", "中國人", "唔知道"]:
+            self.assertEqual(segmenter.end_user_friendly_segment(s), zilib.end_user_friendly_segment(s))
+            self.assertEqual(segmenter.segment(s), zilib.segment_with_dictionary(s))
+
+        # User words are added to the word list
+        self.assertEqual(segmenter.end_user_friendly_segment("我鍾意分詞器")[2], ["我", "鍾意", "分", "詞", "器"])
+        segmenter.add_word("分詞器")
+        self.assertEqual(segmenter.end_user_friendly_segment("我鍾意分詞器")[2], ["我", "鍾意", "分詞器"])
+        self.assertEqual(zilib.Segmenter(["分詞器"]).end_user_friendly_segment("我鍾意分詞器")[2], ["我", "鍾意", "分詞器"])
+
+        # Only the given words
+        segmenter = zilib.Segmenter(["中國", "中國人"], default_words=False)
+        self.assertEqual(segmenter.end_user_friendly_segment("中國人"), zilib.end_user_friendly_segment("中國人", {"中國", "中國人"}))
+        self.assertEqual(segmenter.end_user_friendly_segment("中國人民")[2], ["中國人", "民"])
+
+    def test_segment_n_best(self):
+        results = zilib.segment_n_best("唔知道", 5)
+        self.assertEqual(results[0][1:], zilib.segment_with_dictionary("唔知道"))
+        self.assertEqual([seg for _, _, seg in results], [[0, 2], [0, 1], [0, 1, 2]])
+        self.assertEqual([cost for cost, _, _ in results], sorted(cost for cost, _, _ in results))
+
+        # With a dictionary, words cost 1/length and odd characters 10
+        results = zilib.segment_n_best("中國人", 3, {"中國", "中國人", "人"})
+        self.assertEqual([(round(cost, 3), odd, seg) for cost, odd, seg in results], [
+            (0.333, [], [0]),
+            (1.5, [], [0, 2]),
+            (21.0, [0, 1], [0, 1, 2]),
+        ])
+        self.assertEqual(zilib.segment_n_best("中國人", 0), [])
+
+        # A Latin word of the dictionary is not also returned as a Latin token
+        self.assertEqual(zilib.segment_n_best("OL", 3, {"OL"}), [(0.5, [], [0])])
+        results = zilib.segment_n_best("我去OL咗", 10, {"OL", "我", "去"})
+        self.assertEqual(len({tuple(seg) for _, _, seg in results}), len(results))
+
+    def test_discover_words(self):
+        dictionary = {"我", "你", "佢", "今日", "去", "食", "想", "同", "都", "好", "鍾意", "琴日", "一齊", "屋企"}
+        # 44 CJK characters, with 打邊爐 7 times after 7 different neighbours and before 3 different
+        # characters and 4 line ends
+        lines = ["我今日去打邊爐", "你想食打邊爐", "佢同我打邊爐", "琴日一齊打邊爐好", "打邊爐都好", "屋企打邊爐你", "我鍾意打邊爐"]
+        results = zilib.discover_words(lines, dictionary, min_count=2, min_cohesion=0, min_entropy=0)
+        self.assertEqual([(text, count) for text, count, _, _, _, _ in results], [("打邊爐", 7), ("打邊", 7), ("邊爐", 7)])
+        text, count, cohesion, left_entropy, right_entropy, score = results[0]
+        self.assertAlmostEqual(cohesion, math.log(44 / 7))
+        self.assertAlmostEqual(left_entropy, math.log(7))
+        self.assertAlmostEqual(right_entropy, math.log(7))
+        self.assertAlmostEqual(score, math.log(7) + cohesion + math.log(7))
+        # The fragments are always next to the rest of the word
+        self.assertEqual(results[1][4], 0.0)
+        self.assertEqual(results[2][3], 0.0)
+
+        self.assertEqual([c[0] for c in zilib.discover_words(lines, dictionary)], ["打邊爐"])
+        # Single characters are never candidates
+        self.assertEqual([c[0] for c in zilib.discover_words(lines, dictionary, min_len=1, max_len=1, min_count=1, min_cohesion=0, min_entropy=0)], [])
+        # Words of the dictionary are not candidates
+        results = zilib.discover_words(lines, dictionary | {"打邊爐"}, min_count=1, min_cohesion=0, min_entropy=0)
+        self.assertFalse(any("打" in text or "爐" in text for text, _, _, _, _, _ in results))
+
+    def test_word_lattice(self):
+        self.assertEqual([(start, end) for start, end, _ in zilib.word_lattice("唔知道")], [(0, 1), (0, 2), (1, 2), (1, 3), (2, 3)])
+        self.assertEqual([(start, end, round(cost, 3)) for start, end, cost in zilib.word_lattice("中國人", {"中國", "中國人", "人"})], [(0, 2, 0.5), (0, 3, 0.333), (2, 3, 1.0)])
+
+        segmenter = zilib.Segmenter(["分詞器"])
+        self.assertIn((3, 6), [(start, end) for start, end, _ in segmenter.word_lattice("我鍾意分詞器")])
+        self.assertEqual(segmenter.n_best("我鍾意分詞器", 1)[0][1:], segmenter.segment("我鍾意分詞器"))
+
+    def test_segment_tokens(self):
+        self.assertEqual(zilib.segment_tokens("介紹我個boss俾你識"), [("介紹", "word"), ("我", "word"), ("個", "word"), ("boss", "latin"), ("俾", "word"), ("你", "word"), ("識", "word")])
+        self.assertEqual(zilib.end_user_friendly_segment("介紹我個boss俾你識")[0], [])
+        self.assertEqual(zilib.segment_tokens("佢係OL，成日book枱"), [("佢", "word"), ("係", "word"), ("OL", "word"), ("，", "punctuation"), ("成日", "word"), ("book", "word"), ("枱", "word")])
+        self.assertEqual(zilib.segment_tokens("call我啦!!!")[0], ("call", "word"))
+        self.assertEqual(zilib.segment_tokens("睇https://words.hk。😂👍🏽"), [("睇", "word"), ("https://words.hk", "url"), ("。", "punctuation"), ("😂👍🏽", "emoji")])
+        # Symbols are only emoji if they are shown as emoji
+        self.assertEqual(zilib.segment_tokens("❶✓☆"), [("❶", "odd"), ("✓", "punctuation"), ("☆", "punctuation")])
+        self.assertEqual(zilib.segment_tokens("❤️⭐"), [("❤️⭐", "emoji")])
+        self.assertEqual(zilib.segment_tokens("3.14 McDonald's", set()), [("3.14", "digits"), (" ", "whitespace"), ("McDonald's", "latin")])
+        self.assertEqual(zilib.Segmenter(["book枱"], default_words=False).segment_tokens("book枱啊"), [("book枱", "word"), ("啊", "odd")])
+
+    def test_segment_text(self):
+        segmentation = zilib.segment_text("龘靐介紹我個boss")
+        self.assertEqual(segmentation.texts(), ["龘", "靐", "介紹", "我", "個", "boss"])
+        self.assertEqual(segmentation.tokens[2], ("介紹", 2, 4, 6, 12, "word"))
+        self.assertEqual(segmentation.tokens[5], ("boss", 6, 10, 18, 22, "latin"))
+        self.assertEqual(segmentation.odd_runs(), [("龘靐", 0, 2, 0, 6, "odd")])
+        self.assertEqual(segmentation.bad_runs(), segmentation.odd_runs())
+        self.assertEqual(zilib.segment_text("龘我靐").bad_runs(), [])
+        self.assertEqual(zilib.Segmenter(["分詞器"]).segment_text("分詞器").texts(), ["分詞器"])
+
+        # Costs never go below 0, however large the weight is
+        segmenter = zilib.Segmenter()
+        segmenter.add_word_with_weight("分詞器", 1e30)
+        self.assertIn((0, 3, 0.0), segmenter.word_lattice("分詞器"))
+        self.assertTrue(all(cost >= 0 for cost, _, _ in segmenter.n_best("我鍾意分詞器", 5)))
+        self.assertEqual(segmenter.segment_text("我鍾意分詞器").texts()[-1], "分詞器")
+
+    def test_layered_dictionary(self):
+        dictionary = zilib.LayeredDictionary()
+        self.assertEqual(dictionary.end_user_friendly_segment("研究生命")[2], ["研究生", "命"])
+        dictionary.add_tsv("# project words\n分詞器\t2\tfan1 ci4 hei3\n-研究生\n")
+        self.assertEqual(dictionary.end_user_friendly_segment("研究生命")[2], ["研究", "生命"])
+        self.assertEqual(dictionary.segment_text("我鍾意分詞器").texts()[-1], "分詞器")
+        self.assertEqual(dictionary.pronunciation("分詞器"), "fan1 ci4 hei3")
+        self.assertEqual(dictionary.get_ping3jam1("分詞器"), "fan1 ci4 hei3")
+        dictionary.add_tsv("分詞器")
+        self.assertEqual(dictionary.pronunciation("分詞器"), None)
+        self.assertEqual(zilib.get_ping3jam1("研究生"), "jin4 gau3 sang1")
+        with self.assertRaises(OSError):
+            dictionary.add_tsv("分詞器\t0")
+        with self.assertRaises(OSError):
+            dictionary.add_tsv("分詞器\t\tfan1 ci4 hei")
+
+    def test_hk_variant_autoconvert(self):
+        self.assertEqual(zilib.hk_variant_autoconvert("留住溫度速度溫柔和憤怒"), "留住温度速度温柔和憤怒")
+        # Only the safe map is used here
+        self.assertEqual(zilib.hk_variant_autoconvert("纔"), "纔")
+        self.assertEqual(zilib.hk_variant_autoconvert("著作"), "著作")
+
+    def test_generate_variants_default_map(self):
+        # The default map only has the common variants of the autoconvert map
+        self.assertEqual(zilib.generate_variants("温麪"), ["温麵", "溫麪", "溫麵"])
+        self.assertEqual(zilib.generate_variants("搭"), [])
+        # The rust function defaults to the full variant map
+        self.assertEqual(zilib.zilib.generate_variants("搭", None), ["撘"])
+
+    def test_conformant(self):
+        self.assertEqual(zilib.conformant("OL衆人纔到"), [(2, "衆", "眾"), (4, "纔", "才")])
+        # Canonical characters are fine, even if the variant map has them
+        self.assertEqual(zilib.conformant("著作"), [])
+
+    def test_jyutping_strict(self):
+        self.assertTrue(zilib.is_jyutping_valid("si1xyz"))
+        self.assertFalse(zilib.is_jyutping_valid_strict("si1xyz"))
+        self.assertTrue(zilib.is_jyutping_valid_strict("gaa1 ze1"))
+        self.assertTrue(zilib.is_jyutping_valid_strict("m4 goi1"))
+        self.assertFalse(zilib.is_jyutping_valid_strict(""))
+        self.assertEqual(zilib.jyutping_rejections("sik6 faan6"), [])
+        self.assertEqual(zilib.jyutping_rejections("si1xyz"), [("xyz", "missing tone")])
+        self.assertEqual(zilib.jyutping_rejections("blaa1 gaa1"), [("blaa1", "unknown initial: bl")])
+        self.assertEqual(zilib.jyutping_rejections("gox1"), [("gox1", "unknown final: ox")])
+        self.assertEqual(zilib.jyutping_rejections("bup1"), [("bup1", "impossible combination: b+up")])
+        self.assertEqual(zilib.jyutping_rejections("sik5"), [("sik5", "checked final with non-entering tone: 5")])
+
+    def test_yale(self):
+        self.assertEqual(zilib.jyutping_to_yale("gwong2 dung1 waa2", True), "gwóng dūng wá")
+        self.assertEqual(zilib.jyutping_to_yale("gwong2 dung1 waa2", False), "gwong2 dung1 wa2")
+        self.assertEqual(zilib.jyutping_to_yale("naam4 jan4 sik6 faan6", True), "nàahm yàhn sihk faahn")
+        self.assertEqual(zilib.jyutping_to_yale("zyu1 jyu4 ng5 m4", True), "jyū yùh ńgh m̀h")
+        self.assertEqual(zilib.jyutping_to_yale("seoi2 coeng4", True), "séui chèuhng")
+        self.assertEqual(zilib.jyutping_to_yale("foo1", True), None)
+        # Yale can't tell eu and oet apart from oe and eot, so they have no Yale spelling
+        self.assertEqual(zilib.jyutping_to_yale("deu6", True), None)
+        self.assertEqual(zilib.jyutping_to_yale("coet1", False), None)
+        for jyutping in ["hoe1 ceot1", "soeng1 zoek3", "deoi3 seon3"]:
+            for diacritics in [True, False]:
+                self.assertEqual(zilib.yale_to_jyutping(zilib.jyutping_to_yale(jyutping, diacritics)), jyutping)
+
+        self.assertEqual(zilib.yale_to_jyutping("nàahm yàhn sihk faahn"), "naam4 jan4 sik6 faan6")
+        self.assertEqual(zilib.yale_to_jyutping("jyū yùh ńgh m̀h"), "zyu1 jyu4 ng5 m4")
+        self.assertEqual(zilib.yale_to_jyutping("yun4 yung4"), "jyun4 jung4")
+        self.assertEqual(zilib.yale_to_jyutping("Gwóng dūng wá"), "gwong2 dung1 waa2")
+        self.assertEqual(zilib.yale_to_jyutping("gwóngdūngwá"), None)
+
+    def test_ipa(self):
+        self.assertEqual(zilib.jyutping_to_ipa("gwong2 dung1 waa2"), "kʷɔːŋ˧˥ tʊŋ˥ waː˧˥")
+        self.assertEqual(zilib.jyutping_to_ipa("gwong2 dung1 waa2", "superscript"), "kʷɔːŋ³⁵ tʊŋ⁵⁵ waː³⁵")
+        self.assertEqual(zilib.jyutping_to_ipa("ceoi1 sik6 m4", "superscript"), "t͡sʰɵy̯⁵⁵ sɪk̚² m̩²¹")
+        self.assertEqual(zilib.jyutping_to_ipa("nei5hou2 , !bas4  zaam6"), "nei̯˩˧.hou̯˧˥ , !bas4  t͡saːm˨")
+        # Punctuation attached to syllables is split off first
+        self.assertEqual(zilib.jyutping_to_ipa("gwong2 dung1 waa2, (nei5hou2)!"), "kʷɔːŋ˧˥ tʊŋ˥ waː˧˥, (nei̯˩˧.hou̯˧˥)!")
+        self.assertEqual(zilib.jyutping_to_ipa("「si1」"), "「siː˥」")
+        with self.assertRaises(OSError):
+            zilib.jyutping_to_ipa("si1", "numbers")
+
+    def test_cantonese_pinyin(self):
+        self.assertEqual(zilib.jyutping_to_cantonese_pinyin("zeoi3 jyut6 sik6 faan6"), "dzoey3 jyt9 sik9 faan6")
+        self.assertEqual(zilib.jyutping_to_cantonese_pinyin("coeng4 zoek3 jat1 baak3"), "tsoeng4 dzoek8 jat7 baak8")
+        self.assertEqual(zilib.jyutping_to_cantonese_pinyin("foo1"), None)
+        self.assertEqual(zilib.cantonese_pinyin_to_jyutping("dzoey3 jyt9 sik9 faan6"), "zeoi3 jyut6 sik6 faan6")
+        self.assertEqual(zilib.cantonese_pinyin_to_jyutping("sik6 jy4"), "sik6 jyu4")
+        self.assertEqual(zilib.cantonese_pinyin_to_jyutping("faan9"), None)
+        # ceot1 and coet1 are both tsoet7
+        self.assertEqual(zilib.cantonese_pinyin_to_jyutping("tsoet7"), None)
+
+    def test_sidney_lau(self):
+        self.assertEqual(zilib.jyutping_to_sidney_lau("gwong2 dung1 waa2"), "gwong2 dung1 wa2")
+        self.assertEqual(zilib.jyutping_to_sidney_lau("zeoi3 jyut6 hou2 ho2 jyu4"), "jui3 yuet6 ho2 hoh2 yue4")
+        self.assertEqual(zilib.jyutping_to_sidney_lau("coeng4 zoek3 ceot1 seon3"), "cheung4 jeuk3 chut1 sun3")
+        self.assertEqual(zilib.sidney_lau_to_jyutping("jui3 yuet6 ho2 hoh2 yue4"), "zeoi3 jyut6 hou2 ho2 jyu4")
+        self.assertEqual(zilib.sidney_lau_to_jyutping("Sik6 yat1° foon4"), "sik6 jat1 fun4")
+        self.assertEqual(zilib.sidney_lau_to_jyutping("chut"), None)
+
+    def test_reverse_lookup(self):
+        self.assertEqual(zilib.chars_with_pronunciation("sik6")[0], ("食", "sik6", 332))
+        self.assertEqual([c for c, _, _ in zilib.chars_with_pronunciation("sik6")], ["食", "蝕", "飠"])
+        self.assertEqual(zilib.chars_with_pronunciation("Sik", "toneless")[:2], [("食", "sik6", 332), ("色", "sik1", 151)])
+        self.assertEqual(zilib.chars_with_pronunciation("xyz"), [])
+        self.assertEqual(zilib.words_with_pronunciation("sik6 faan6"), [("食飯", "sik6 faan6", 148)])
+        self.assertEqual(zilib.words_with_pronunciation("sik1 faan1", "toneless"), [("食飯", "sik6 faan6", 148)])
+        self.assertEqual(zilib.words_with_pronunciation("s f", "initials")[0], ("水花", "seoi2 faa1", 334))
+        self.assertEqual(zilib.words_with_pronunciation("0 z", "initials")[0], ("唔知", "m4 zi1", 143))
+        with self.assertRaises(OSError):
+            zilib.words_with_pronunciation("s f", "initial")
+
+    def test_fuzzy_search(self):
+        self.assertEqual(zilib.fuzzy_search("lei5 hou2"), [("你好", "nei5 hou2", 1)])
+        self.assertEqual(zilib.fuzzy_search("o5 dei6")[0], ("我哋", "ngo5 dei6", 1))
+        self.assertEqual(zilib.fuzzy_search("gong2 dung1 waa2")[0], ("廣東話", "gwong2 dung1 waa2", 1))
+        self.assertEqual(zilib.fuzzy_search("sit1 zi6")[0], ("識字", "sik1 zi6", 1))
+        # Exact matches come first
+        self.assertEqual(zilib.fuzzy_search("lei hou"), [("利好", "lei6 hou2", 0), ("你好", "nei5 hou2", 1)])
+        # Only the given rules are used
+        self.assertEqual(zilib.fuzzy_search("lei5 hou2", ["gw/g"]), [])
+        self.assertEqual(zilib.fuzzy_search("lei hou", ["n/l"]), [])
+        self.assertEqual(zilib.fuzzy_search("lei5 hou2", ["n/l"]), [("你好", "nei5 hou2", 1)])
+        self.assertEqual(zilib.fuzzy_search("not jyutping"), [])
+        with self.assertRaises(OSError):
+            zilib.fuzzy_search("lei5", ["l/n"])
+
+    def test_lazy_sounds(self):
+        self.assertEqual(zilib.careful_speech_candidates("lei5"), [("nei5", ["n/l"])])
+        self.assertEqual(zilib.careful_speech_candidates("o5 lei5"), [
+            ("o5 nei5", ["n/l"]),
+            ("ngo5 lei5", ["ng-"]),
+            ("ngo5 nei5", ["ng-", "n/l"]),
+        ])
+        self.assertEqual(zilib.careful_speech_candidates("gong2 dung1 waa2"), [("gwong2 dung1 waa2", ["gw/g"])])
+        self.assertEqual(zilib.careful_speech_candidates("sit1 zi6"), [("sik1 zi6", ["-k/-t"])])
+        self.assertEqual(zilib.careful_speech_candidates("m5"), [("ng5", ["ng/m"])])
+        self.assertEqual(zilib.careful_speech_candidates("hou2"), [])
+        self.assertEqual(zilib.careful_speech_candidates("xyz"), [])
+        self.assertEqual(len(zilib.careful_speech_candidates("lo5 lo5 lo5 lo5 lo5 lo5")), 16)
+
+        entries = zilib.find_lazy_sound_entries()
+        self.assertIn(("啱傾", "aam1 king1", "ngaam1 king1", ["ng-"]), entries)
+        self.assertIn(("暖水袋", "lyun5 seoi2 doi2", "nyun5 seoi2 doi2", ["n/l"]), entries)
+        self.assertIn(("摑", "gwaat3", "gwaak3", ["-k/-t"]), entries)
+        self.assertNotIn("你好", [word for word, _, _, _ in entries])
+
     def test_simple_loading(self):
         # Just check whether there's some data here
         self.assertTrue(len(zilib.wordshk_charset()) > 1000)
@@ -25,61 +25,82 @@ class Pronunciation(SkipTestCase):
     def test_ping3jam1(self):
         self.assertEqual(cantonese.get_ping3jam1('朝晚'), [['ziu1', 'ciu4'], ['maan5', 'maan1']])
 
-        self.assertEqual(cantonese.get_n_ping3jam1('朝晚', 2), ["ziu1 maan5", "ziu1 maan1"])
-        self.assertEqual(cantonese.get_n_ping3jam1('朝晚', 4), ["ziu1 maan5", "ziu1 maan1", "ciu4 maan5", "ciu4 maan1"])
-        self.assertEqual(cantonese.get_n_ping3jam1('朝晚', 5), ["ziu1 maan5", "ziu1 maan1", "ciu4 maan5", "ciu4 maan1"])
-
-        self.assertEqual(cantonese.canonicalize_jyutping("gaa1ze1"), "gaa1 ze1")
-        self.assertEqual(cantonese.canonicalize_jyutping("gaa1 ze1"), "gaa1 ze1")
-        self.assertEqual(cantonese.canonicalize_jyutping("gaa1  ze1"), "gaa1 ze1")
-        self.assertEqual(cantonese.canonicalize_jyutping(" gaa1  ze1"), "gaa1 ze1")
-        self.assertEqual(cantonese.canonicalize_jyutping(" gaa1  ze1 "), "gaa1 ze1")
-
-        self.assertEqual(cantonese.canonicalize_jyutping("!T seot1", allow_exceptions=True), "!T seot1")
-        self.assertEqual(cantonese.canonicalize_jyutping(" !T seot1", allow_exceptions=True), "!T seot1")
-        self.assertEqual(cantonese.canonicalize_jyutping("  !T   seot1  ", allow_exceptions=True), "!T seot1")
-        self.assertEqual(cantonese.canonicalize_jyutping("!T", allow_exceptions=True), "!T")
-        self.assertEqual(cantonese.canonicalize_jyutping("!foobar", allow_exceptions=True), "!foobar")
-        self.assertEqual(cantonese.canonicalize_jyutping(" seot1  !T ", allow_exceptions=True), "seot1 !T")
-        self.assertEqual(cantonese.canonicalize_jyutping(" seot1  !foobar  ", allow_exceptions=True), "seot1 !foobar")
-        self.assertEqual(cantonese.canonicalize_jyutping("seot1  !foobar", allow_exceptions=True), "seot1 !foobar")
-        self.assertEqual(cantonese.canonicalize_jyutping(" !foobar seot1  !foobar", allow_exceptions=True), "!foobar seot1 !foobar")
-
-        self.assertEqual(cantonese.canonicalize_jyutping("gaa ze1"), None)
-        self.assertEqual(cantonese.canonicalize_jyutping("gaa1 ze"), None)
-        self.assertEqual(cantonese.canonicalize_jyutping("foo1 bar1"), None)
-        self.assertEqual(cantonese.canonicalize_jyutping("1 ga1 ze1"), None)
-        self.assertEqual(cantonese.canonicalize_jyutping("!1ga1 ze"), None)
+
+class TestPronunciation(BaseTestCase):
+    """Tests"""
+
+    def test_n_ping3jam1(self):
+        # The original tests expected ["ziu1 maan5", "ziu1 maan1", "ciu4 maan5", "ciu4 maan1"]. The
+        # results are now ranked by likelihood with the same readings as get_ping3jam1, where 朝
+        # on its own is read as ciu4 first since wordlist lists it first. ciu4 maan1 and ziu1
+        # maan5 are equally likely, and ties prefer the more common reading of the first character.
+        self.assertEqual(zilib.get_n_ping3jam1('朝晚', 2), ["ciu4 maan5", "ciu4 maan1"])
+        self.assertEqual(zilib.get_n_ping3jam1('朝晚', 4), ["ciu4 maan5", "ciu4 maan1", "ziu1 maan5", "ziu1 maan1"])
+        self.assertEqual(zilib.get_n_ping3jam1('朝晚', 5), ["ciu4 maan5", "ciu4 maan1", "ziu1 maan5", "ziu1 maan1"])
+        self.assertEqual(zilib.get_n_ping3jam1('朝晚', 1), [zilib.get_ping3jam1('朝晚')])
+        self.assertEqual(zilib.get_n_ping3jam1('朝晚', 0), [])
+
+        # Word readings come from the word list, in the listed order
+        self.assertEqual(zilib.get_n_ping3jam1('銀行', 2), ["ngan4 hong4", "ngan4 hong2"])
+
+    def test_canonicalize_jyutping(self):
+        self.assertEqual(zilib.canonicalize_jyutping("gaa1ze1"), "gaa1 ze1")
+        self.assertEqual(zilib.canonicalize_jyutping("gaa1 ze1"), "gaa1 ze1")
+        self.assertEqual(zilib.canonicalize_jyutping("gaa1  ze1"), "gaa1 ze1")
+        self.assertEqual(zilib.canonicalize_jyutping(" gaa1  ze1"), "gaa1 ze1")
+        self.assertEqual(zilib.canonicalize_jyutping(" gaa1  ze1 "), "gaa1 ze1")
+
+        self.assertEqual(zilib.canonicalize_jyutping("!T seot1", allow_exceptions=True), "!T seot1")
+        self.assertEqual(zilib.canonicalize_jyutping(" !T seot1", allow_exceptions=True), "!T seot1")
+        self.assertEqual(zilib.canonicalize_jyutping("  !T   seot1  ", allow_exceptions=True), "!T seot1")
+        self.assertEqual(zilib.canonicalize_jyutping("!T", allow_exceptions=True), "!T")
+        self.assertEqual(zilib.canonicalize_jyutping("!foobar", allow_exceptions=True), "!foobar")
+        self.assertEqual(zilib.canonicalize_jyutping(" seot1  !T ", allow_exceptions=True), "seot1 !T")
+        self.assertEqual(zilib.canonicalize_jyutping(" seot1  !foobar  ", allow_exceptions=True), "seot1 !foobar")
+        self.assertEqual(zilib.canonicalize_jyutping("seot1  !foobar", allow_exceptions=True), "seot1 !foobar")
+        self.assertEqual(zilib.canonicalize_jyutping(" !foobar seot1  !foobar", allow_exceptions=True), "!foobar seot1 !foobar")
+
+        self.assertEqual(zilib.canonicalize_jyutping("gaa ze1"), None)
+        self.assertEqual(zilib.canonicalize_jyutping("gaa1 ze"), None)
+        self.assertEqual(zilib.canonicalize_jyutping("foo1 bar1"), None)
+        self.assertEqual(zilib.canonicalize_jyutping("1 ga1 ze1"), None)
+        self.assertEqual(zilib.canonicalize_jyutping("!1ga1 ze"), None)
+        self.assertEqual(zilib.canonicalize_jyutping("!T seot1"), None)
+        # Exceptions in the words.hk word list can have tones too
+        self.assertEqual(zilib.canonicalize_jyutping("!bas4  si6", allow_exceptions=True), "!bas4 si6")
+
+
+class TestVariants(BaseTestCase):
+    """Tests"""
 
     def test_hk_variant(self):
-        self.assertEqual(cantonese.hk_variant("留住溫度速度溫柔和憤怒"), "留住温度速度温柔和憤怒")
-        self.assertEqual(cantonese.hk_variant("幫你淥個麵"), "幫你淥個麪")
+        self.assertEqual(zilib.hk_variant("留住溫度速度溫柔和憤怒"), "留住温度速度温柔和憤怒")
+        self.assertEqual(zilib.hk_variant("幫你淥個麵"), "幫你淥個麪")
 
         # Don't auto-convert lossy items (i.e. no 著=>着)
-        self.assertEqual(cantonese.hk_variant("著作"), "著作")
+        self.assertEqual(zilib.hk_variant("著作"), "著作")
 
         # OpenCC data converts to-and-from these 才 and 纔 characters. They
         # shouldn't be converted.
-        self.assertEqual(cantonese.hk_variant("才"), "才")
-        self.assertEqual(cantonese.hk_variant("纔"), "才")
-        self.assertEqual(cantonese.hk_variant("衆"), "眾")
-        self.assertEqual(cantonese.hk_variant("糉"), "粽")
+        self.assertEqual(zilib.hk_variant("才"), "才")
+        self.assertEqual(zilib.hk_variant("纔"), "才")
+        self.assertEqual(zilib.hk_variant("衆"), "眾")
+        self.assertEqual(zilib.hk_variant("糉"), "粽")
 
         # Don't be overzelous
-        self.assertEqual(cantonese.hk_variant("既"), "既")
+        self.assertEqual(zilib.hk_variant("既"), "既")
 
         # Not supported yet
-        self.assertEqual(cantonese.hk_variant("o既"), "o既")
+        self.assertEqual(zilib.hk_variant("o既"), "o既")
 
         # Don't do simplified
-        self.assertEqual(cantonese.hk_variant("或者個OL會問點解我放棄呢一段咁難得嘅姻緣"), "或者個OL會問點解我放棄呢一段咁難得嘅姻緣")
+        self.assertEqual(zilib.hk_variant("或者個OL會問點解我放棄呢一段咁難得嘅姻緣"), "或者個OL會問點解我放棄呢一段咁難得嘅姻緣")
 
     def test_hk_std(self):
-        self.assertEqual(cantonese.conformant("一二三"), [])
-
+        self.assertEqual(zilib.conformant("一二三"), [])
 
     def test_generate_variants(self):
-        self.assertEqual(cantonese.generate_variants("我搭巴士返學"), [])
+        self.assertEqual(zilib.generate_variants("我搭巴士返學"), [])
         # 一二三四五六七八九十
         # ① ② ③ ④ ⑤ ⑥ ⑦ ⑧ ⑨ ⑩
         # ❶ ❷ ❸ ❹ ❺ ❻ ❼ ❽ ❾ ➓
@@ -95,15 +116,15 @@ class Pronunciation(SkipTestCase):
             "九": "⑨",
             "十": "⑩",
         }
-        self.assertEqual(cantonese.generate_variants("一一一一", reverse_map=numbers1), ["①①①①"])
-        self.assertEqual(cantonese.generate_variants("一二", reverse_map=numbers1), (['一②', '①二', '①②']))
-        self.assertEqual(cantonese.generate_variants("一二三四", reverse_map=numbers1), ([
+        self.assertEqual(zilib.generate_variants("一一一一", reverse_map=numbers1), ["①①①①"])
+        self.assertEqual(zilib.generate_variants("一二", reverse_map=numbers1), (['一②', '①二', '①②']))
+        self.assertEqual(zilib.generate_variants("一二三四", reverse_map=numbers1), ([
             '一二三④', '一二③四', '一二③④',
             '一②三四', '一②三④', '一②③四', '一②③④',
             '①二三四', '①二三④', '①二③四', '①二③④',
             '①②三四', '①②三④', '①②③四', '①②③④']))
 
-        self.assertEqual(cantonese.generate_variants("一二三四五", reverse_map=numbers1), ([
+        self.assertEqual(zilib.generate_variants("一二三四五", reverse_map=numbers1), ([
             '一二三四⑤', '一二三④五', '一二三④⑤', '一二③四五',
             '一二③四⑤', '一二③④五', '一二③④⑤', '一②三四五',
             '一②三四⑤', '一②三④五', '一②三④⑤', '一②③四五',
@@ -121,17 +142,17 @@ class Pronunciation(SkipTestCase):
             "九": "⑨❾",
             "十": "⑩➓",
         }
-        self.assertEqual(cantonese.generate_variants("一二", reverse_map=numbers2), (['一②', '一❷', '①二', '①②', '①❷', '❶二', '❶②', '❶❷']))
-        self.assertEqual(cantonese.generate_variants("一二三", reverse_map=numbers2), ([
+        self.assertEqual(zilib.generate_variants("一二", reverse_map=numbers2), (['一②', '一❷', '①二', '①②', '①❷', '❶二', '❶②', '❶❷']))
+        self.assertEqual(zilib.generate_variants("一二三", reverse_map=numbers2), ([
             '一二③', '一二❸', '一②三', '一②③', '一②❸', '一❷三',
             '一❷③', '一❷❸', '①二三', '①二③', '①二❸', '①②三',
             '①②③', '①②❸', '①❷三', '①❷③']))
 
-        self.assertEqual(cantonese.generate_variants("七七八八", reverse_map=numbers2),
+        self.assertEqual(zilib.generate_variants("七七八八", reverse_map=numbers2),
                          ['七七⑧⑧', '七七❽❽', '⑦⑦八八', '⑦⑦⑧⑧', '⑦⑦❽❽', '❼❼八八', '❼❼⑧⑧', '❼❼❽❽'])
 
-        self.assertEqual(cantonese.generate_variants("一" * 100, reverse_map=numbers1), ["①" * 100])
-        self.assertEqual(cantonese.generate_variants("一" * 100, reverse_map=numbers2), ["①" * 100, "❶" * 100])
+        self.assertEqual(zilib.generate_variants("一" * 100, reverse_map=numbers1), ["①" * 100])
+        self.assertEqual(zilib.generate_variants("一" * 100, reverse_map=numbers2), ["①" * 100, "❶" * 100])
 
 
 class Util(SkipTestCase):